@@ -1,30 +1,333 @@
 use {
-    futures::{future, pin_mut, StreamExt},
-    tokio_tungstenite::{connect_async, tungstenite::protocol::Message},
+    anyhow::Context,
+    clap::Parser,
+    console::style,
+    futures::{
+        channel::{mpsc, oneshot},
+        sink::SinkExt,
+        stream::StreamExt,
+    },
+    rustyline::{error::ReadlineError, DefaultEditor, ExternalPrinter},
+    serde_json::{json, Value},
+    std::{
+        collections::HashMap,
+        fs, io,
+        path::{Path, PathBuf},
+    },
+    tokio::{net::TcpStream, time::Instant},
+    tokio_tungstenite::{
+        connect_async, tungstenite::protocol::Message, MaybeTlsStream, WebSocketStream,
+    },
 };
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let (stdin_tx, stdin_rx) = futures::channel::mpsc::unbounded();
-    let text = r#"{"id":1,"jsonrpc":"2.0","method":"slotSubscribe","params":[]}"#.to_owned();
-    stdin_tx.unbounded_send(Message::text(text))?;
-    tokio::spawn(async move {
-        tokio::time::sleep(tokio::time::Duration::from_secs(100)).await;
-    });
+const HELP: &str = r#"Enter raw JSON-RPC request, `jsonrpc` and `id` are added if missing:
+  {"method":"getVersion"}
+Shortcuts:
+  :sub <kind> [params]  subscribe, e.g. `:sub slot` or `:sub account ["<pubkey>"]`
+  :unsub <id>           unsubscribe from subscription created in this session
+  :ping                 send WebSocket ping and measure round trip
+  :help                 show this message
+  :quit                 close connection and exit"#;
 
-    let (ws_stream, _) = connect_async("ws://127.0.0.1:8000/streams").await?;
+#[derive(Debug, Clone, Parser)]
+#[clap(about = "Interactive JSON-RPC console for Whirligig")]
+struct Args {
+    /// WebSocket endpoint
+    #[clap(short, long, default_value_t = String::from("ws://127.0.0.1:8000/"))]
+    endpoint: String,
 
-    let (write, read) = ws_stream.split();
+    /// File to load readline history from, history is saved back on exit
+    #[clap(long)]
+    history: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+enum Command {
+    Send(Message),
+    Print(String),
+    Quit,
+}
+
+/// Print above the readline prompt, plain stdout when input is not a terminal
+struct Printer(Option<Box<dyn ExternalPrinter + Send>>);
+
+impl Printer {
+    fn print(&mut self, text: String) -> anyhow::Result<()> {
+        match &mut self.0 {
+            Some(printer) => printer.print(text)?,
+            None => println!("{text}"),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct Session {
+    next_request_id: u64,
+    // request id to subscription kind, e.g. `slot`
+    pending_subscribe: HashMap<u64, String>,
+    // subscription id to subscription kind
+    subscriptions: HashMap<u64, String>,
+    ping_sent: Option<Instant>,
+}
 
-    let stdin_to_ws = stdin_rx.map(Ok).forward(write);
-    let ws_to_stdout = {
-        read.for_each(|message| async move {
-            println!("{:?}", message.unwrap());
+impl Session {
+    fn handle_line(&mut self, line: &str) -> anyhow::Result<Option<Command>> {
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(None);
+        }
+        let Some(shortcut) = line.strip_prefix(':') else {
+            let request = serde_json::from_str(line)?;
+            return Ok(Some(self.send_request(request)));
+        };
+
+        let (name, rest) = shortcut
+            .split_once(char::is_whitespace)
+            .map(|(name, rest)| (name, rest.trim()))
+            .unwrap_or((shortcut, ""));
+        Ok(Some(match name {
+            "sub" => {
+                let (kind, params) = rest
+                    .split_once(char::is_whitespace)
+                    .map(|(kind, params)| (kind, params.trim()))
+                    .unwrap_or((rest, ""));
+                anyhow::ensure!(!kind.is_empty(), "subscription kind is required");
+                let params = if params.is_empty() {
+                    json!([])
+                } else {
+                    serde_json::from_str(params)?
+                };
+                self.send_request(json!({
+                    "method": format!("{kind}Subscribe"),
+                    "params": params,
+                }))
+            }
+            "unsub" => {
+                let id = rest
+                    .parse::<u64>()
+                    .map_err(|_| anyhow::anyhow!("invalid subscription id: {rest}"))?;
+                let Some(kind) = self.subscriptions.remove(&id) else {
+                    anyhow::bail!("unknown subscription: {id}");
+                };
+                self.send_request(json!({
+                    "method": format!("{kind}Unsubscribe"),
+                    "params": [id],
+                }))
+            }
+            "ping" => {
+                self.ping_sent = Some(Instant::now());
+                Command::Send(Message::Ping(vec![]))
+            }
+            "help" => Command::Print(HELP.to_owned()),
+            "quit" => Command::Quit,
+            _ => anyhow::bail!("unknown command: {line}, see `:help`"),
+        }))
+    }
+
+    fn send_request(&mut self, mut request: Value) -> Command {
+        if let Some(object) = request.as_object_mut() {
+            object
+                .entry("jsonrpc")
+                .or_insert_with(|| Value::from("2.0"));
+            let id = object.entry("id").or_insert_with(|| {
+                self.next_request_id += 1;
+                Value::from(self.next_request_id)
+            });
+            let id = id.as_u64();
+            let kind = object
+                .get("method")
+                .and_then(Value::as_str)
+                .and_then(|method| method.strip_suffix("Subscribe"));
+            if let (Some(id), Some(kind)) = (id, kind) {
+                self.pending_subscribe.insert(id, kind.to_owned());
+            }
+        }
+        Command::Send(Message::Text(request.to_string()))
+    }
+
+    fn format_message(&mut self, message: Message) -> Option<String> {
+        Some(match message {
+            Message::Text(data) => match serde_json::from_str::<Value>(&data) {
+                Ok(value) => self.format_value(value),
+                Err(_) => format!("{} {data}", style("<- text").magenta()),
+            },
+            Message::Binary(data) => {
+                format!("{} {} bytes", style("<- binary").magenta(), data.len())
+            }
+            Message::Pong(_) => match self.ping_sent.take() {
+                Some(ts) => format!("{} {:?}", style("<- pong").yellow(), ts.elapsed()),
+                None => style("<- pong").yellow().to_string(),
+            },
+            Message::Close(frame) => format!("{} {frame:?}", style("<- close").red()),
+            Message::Ping(_) | Message::Frame(_) => return None,
         })
-    };
+    }
+
+    fn format_value(&mut self, value: Value) -> String {
+        if let (Some(method), Some(params)) = (value["method"].as_str(), value.get("params")) {
+            let header = format!("<- {method} #{}", params["subscription"]);
+            return format!(
+                "{}\n{}",
+                style(header).cyan(),
+                Self::pretty(params.get("result").unwrap_or(params))
+            );
+        }
+
+        let id = &value["id"];
+        if let Some(error) = value.get("error") {
+            return format!(
+                "{}\n{}",
+                style(format!("<- error #{id}")).red(),
+                Self::pretty(error)
+            );
+        }
+
+        let result = value.get("result").unwrap_or(&value);
+        if let (Some(kind), Some(subscription)) = (
+            id.as_u64()
+                .and_then(|id| self.pending_subscribe.remove(&id)),
+            result.as_u64(),
+        ) {
+            self.subscriptions.insert(subscription, kind);
+        }
+        format!(
+            "{}\n{}",
+            style(format!("<- response #{id}")).green(),
+            Self::pretty(result)
+        )
+    }
 
-    pin_mut!(stdin_to_ws, ws_to_stdout);
-    future::select(stdin_to_ws, ws_to_stdout).await;
+    fn pretty(value: &Value) -> String {
+        serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    let (stream, _) = runtime.block_on(connect_async(&args.endpoint))?;
+
+    let mut editor = DefaultEditor::new()?;
+    if let Some(path) = &args.history {
+        load_history(&mut editor, path)?;
+    }
+    let mut printer = Printer(
+        editor
+            .create_external_printer()
+            .ok()
+            .map(|printer| Box::new(printer) as Box<dyn ExternalPrinter + Send>),
+    );
+    printer.print(format!(
+        "connected to {}, type `:help` for help",
+        args.endpoint
+    ))?;
+
+    // readline stays on the main thread so every exit path returns from it,
+    // restores the terminal and saves history, the socket side is a task
+    let (lines_tx, lines_rx) = mpsc::unbounded();
+    let connection = runtime.spawn(process_connection(stream, lines_rx, printer));
+    loop {
+        let line = match editor.readline("> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
+            Err(error) => {
+                eprintln!("failed to read line: {error}");
+                break;
+            }
+        };
+        let _ = editor.add_history_entry(line.as_str());
+
+        // wait until the line is handled, sender is dropped on `:quit` and
+        // when connection is closed
+        let (handled_tx, handled_rx) = oneshot::channel();
+        if lines_tx.unbounded_send((line, handled_tx)).is_err()
+            || runtime.block_on(handled_rx).is_err()
+        {
+            break;
+        }
+    }
+    drop(lines_tx);
+
+    if let Some(path) = &args.history {
+        save_history(&editor, path)?;
+    }
+    runtime.block_on(connection)?
+}
 
+async fn process_connection(
+    mut stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    mut lines_rx: mpsc::UnboundedReceiver<(String, oneshot::Sender<()>)>,
+    mut printer: Printer,
+) -> anyhow::Result<()> {
+    let mut session = Session::default();
+    loop {
+        tokio::select! {
+            line = lines_rx.next() => {
+                let Some((line, handled_tx)) = line else {
+                    break;
+                };
+                match session.handle_line(&line) {
+                    Ok(Some(Command::Send(message))) => {
+                        if let Message::Text(data) = &message {
+                            printer.print(style(format!("-> {data}")).dim().to_string())?;
+                        }
+                        stream.send(message).await?;
+                    }
+                    Ok(Some(Command::Print(text))) => printer.print(text)?,
+                    Ok(Some(Command::Quit)) => break,
+                    Ok(None) => {}
+                    Err(error) => {
+                        printer.print(style(format!("error: {error}")).red().to_string())?;
+                    }
+                }
+                let _ = handled_tx.send(());
+            }
+            message = stream.next() => {
+                // readline is blocked until the next line, ask for it
+                let closed = style("connection closed, press Enter to exit").red();
+                let message = match message {
+                    Some(Ok(message)) => message,
+                    Some(Err(error)) => {
+                        printer.print(closed.to_string())?;
+                        return Err(error.into());
+                    }
+                    None => {
+                        printer.print(closed.to_string())?;
+                        return Ok(());
+                    }
+                };
+                if let Some(text) = session.format_message(message) {
+                    printer.print(text)?;
+                }
+            }
+        }
+    }
+
+    let _ = stream.close(None).await;
     Ok(())
 }
+
+fn load_history(editor: &mut DefaultEditor, path: &Path) -> anyhow::Result<()> {
+    let history = match fs::read_to_string(path) {
+        Ok(history) => history,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(error) => {
+            return Err(error).with_context(|| format!("failed to read history: {path:?}"))
+        }
+    };
+    for line in history.lines() {
+        editor.add_history_entry(line)?;
+    }
+    Ok(())
+}
+
+fn save_history(editor: &DefaultEditor, path: &Path) -> anyhow::Result<()> {
+    let mut history = String::new();
+    for line in editor.history().iter() {
+        history.push_str(line);
+        history.push('\n');
+    }
+    fs::write(path, history).with_context(|| format!("failed to save history: {path:?}"))
+}
@@ -1,14 +1,11 @@
 use {
     anyhow::Context,
     clap::{Parser, Subcommand, ValueEnum},
-    futures::{sink::SinkExt, stream::StreamExt},
+    futures::{channel::mpsc, executor::block_on_stream, future::try_join_all, stream::StreamExt},
     indicatif::{ProgressBar, ProgressStyle},
-    jsonrpc_core::types::response::Output as RpcOutput,
-    serde::Deserialize,
-    serde_json::{json, Value},
+    serde::{Deserialize, Serialize},
     solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig},
     solana_client::{
-        nonblocking::pubsub_client::PubsubClient,
         rpc_config::{
             RpcAccountInfoConfig, RpcBlockSubscribeConfig, RpcBlockSubscribeFilter,
             RpcProgramAccountsConfig, RpcSignatureSubscribeConfig, RpcTransactionLogsConfig,
@@ -16,15 +13,25 @@ use {
         },
         rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType},
     },
-    solana_rpc_client_api::response::RpcVersionInfo,
     solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature},
     solana_transaction_status::{TransactionDetails, UiTransactionEncoding},
-    std::{fmt, str::FromStr},
-    tokio::net::TcpStream,
-    tokio_tungstenite::{
-        connect_async, tungstenite::protocol::Message, MaybeTlsStream, WebSocketStream,
+    std::{
+        collections::HashSet,
+        fmt, fs,
+        path::Path,
+        str::FromStr,
+        time::{SystemTime, UNIX_EPOCH},
+    },
+    tokio::{signal, sync::broadcast::error::RecvError, task::JoinHandle, time::Duration},
+    tracing::{info, warn},
+    whirligig_client::{
+        capture::{CaptureRecord, CaptureWriter},
+        client::{ClientConfig, ReconnectConfig, Subscription, WhirligigClient},
+        types::{
+            TransactionSubscribeConfig, TransactionSubscribeFilter,
+            TransactionSubscribeFilterAccounts, TransactionSubscribeFilterDeprecated,
+        },
     },
-    tracing::info,
 };
 
 #[derive(Debug, Clone, Parser)]
@@ -33,6 +40,10 @@ struct Args {
     #[clap(short, long, default_value_t = String::from("ws://127.0.0.1:8000/"))]
     endpoint: String,
 
+    /// Reconnect on disconnect and re-issue active subscriptions
+    #[clap(long, default_value_t = false)]
+    reconnect: bool,
+
     #[command(subcommand)]
     action: ArgsAction,
 }
@@ -59,13 +70,25 @@ enum ArgsAction {
     Subscribe {
         /// Type of subscription
         #[command(subcommand)]
-        action: SubscribeAction,
+        action: Option<SubscribeAction>,
+        /// TOML or YAML file with named subscriptions, used instead of subscription type
+        #[clap(long)]
+        config: Option<String>,
         /// Commitment level of subscritpion
         #[clap(short, long, default_value_t = SubscribeCommitment::default())]
         commitment: SubscribeCommitment,
         /// Show only progress bar with received messages
         #[clap(long, default_value_t = false)]
         only_counter: bool,
+        /// Output format of received messages
+        #[clap(long, value_enum, default_value_t = OutputFormat::default())]
+        output: OutputFormat,
+        /// Record received WebSocket frames and subscriptions to the capture file
+        #[clap(long)]
+        record: Option<String>,
+        /// Compress capture file with zstd
+        #[clap(long, default_value_t = false, requires = "record")]
+        record_zstd: bool,
     },
     /// Get node version
     GetVersion,
@@ -73,7 +96,8 @@ enum ArgsAction {
     GetVersionWhirligig,
 }
 
-#[derive(Clone, Copy, Default, ValueEnum)]
+#[derive(Clone, Copy, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 enum SubscribeCommitment {
     Processed,
     Confirmed,
@@ -107,7 +131,77 @@ impl From<SubscribeCommitment> for CommitmentConfig {
     }
 }
 
-#[derive(Debug, Clone, Subcommand)]
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+enum OutputFormat {
+    #[default]
+    Debug,
+    Json,
+    PrettyJson,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OutputItem<'a, T> {
+    /// Name of subscription from `--config`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+    kind: &'a str,
+    /// Local receive time, milliseconds since UNIX epoch
+    received_at: u128,
+    item: &'a T,
+}
+
+#[derive(Debug)]
+struct Output {
+    format: OutputFormat,
+    pb: Option<ProgressBar>,
+}
+
+impl Output {
+    fn new(format: OutputFormat, only_counter: bool) -> anyhow::Result<Self> {
+        let pb = only_counter
+            .then(|| {
+                let pb = ProgressBar::new(u64::MAX);
+                pb.set_style(ProgressStyle::with_template(
+                    "{spinner:.green} +{pos} messages",
+                )?);
+                Ok::<_, anyhow::Error>(pb)
+            })
+            .transpose()?;
+        Ok(Self { format, pb })
+    }
+
+    fn on_new_item<T: fmt::Debug + Serialize>(
+        &self,
+        name: Option<&str>,
+        kind: &str,
+        item: &T,
+    ) -> anyhow::Result<()> {
+        if let Some(pb) = &self.pb {
+            pb.inc(1);
+            return Ok(());
+        }
+
+        let output = OutputItem {
+            name,
+            kind,
+            received_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis(),
+            item,
+        };
+        match self.format {
+            OutputFormat::Debug => match name {
+                Some(name) => info!("{name} ({kind}), new item: {item:?}"),
+                None => info!("{kind}, new item: {item:?}"),
+            },
+            OutputFormat::Json => println!("{}", serde_json::to_string(&output)?),
+            OutputFormat::PrettyJson => println!("{}", serde_json::to_string_pretty(&output)?),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Subcommand, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case", deny_unknown_fields)]
 enum SubscribeAction {
     // STABLE
     /// Subscribe on account updates
@@ -126,12 +220,15 @@ enum SubscribeAction {
     Logs {
         /// All transactions
         #[clap(long, default_value_t = false)]
+        #[serde(default)]
         all: bool,
         /// All transactions with votes
         #[clap(long, default_value_t = false)]
+        #[serde(default)]
         all_with_votes: bool,
         /// Only transactions with mentions
         #[clap(long)]
+        #[serde(default)]
         mentions: Vec<String>,
     },
     /// Subscribe on accounts updates owned by program
@@ -141,9 +238,11 @@ enum SubscribeAction {
         pubkey: String,
         /// Filter by data size
         #[clap(long)]
+        #[serde(default)]
         filter_data_size: Vec<u64>,
         /// Filter by memcmp, format: `offset,data in base58`
         #[clap(long)]
+        #[serde(default)]
         filter_memcmp: Vec<String>,
         /// Encoding format
         #[clap(long, short)]
@@ -159,7 +258,7 @@ enum SubscribeAction {
         signature: String,
     },
     /// Subscribe on slot updates
-    Slot,
+    Slot {},
 
     // UNSTABLE
     /// Subscribe on block updates
@@ -180,12 +279,12 @@ enum SubscribeAction {
         #[clap(long, short)]
         max_supported_transaction_version: Option<u8>,
     },
-    // Subscribe on ?
-    // Root {}
-    // Subscribe on different updates of slot
-    // SlotUpdate {}
-    // Subscribe on ?
-    // Vote {}
+    /// Subscribe on root slot updates
+    Root {},
+    /// Subscribe on different updates of slot
+    SlotUpdate {},
+    /// Subscribe on votes observed in gossip
+    Vote {},
 
     // EXPERIMENTAL
     /// Subscribe on transaction updates
@@ -201,12 +300,15 @@ enum SubscribeAction {
         signature: Option<String>,
         /// Transaction should include any of these accounts
         #[clap(long)]
+        #[serde(default)]
         account_include: Vec<String>,
         /// Transaction should not contain any of these accounts
         #[clap(long)]
+        #[serde(default)]
         account_exclude: Vec<String>,
         /// Transaction should contain all these accounts
         #[clap(long)]
+        #[serde(default)]
         account_required: Vec<String>,
         /// Encoding format
         #[clap(long, short)]
@@ -231,17 +333,21 @@ enum SubscribeAction {
         failed: Option<bool>,
         /// Transaction should include any of these accounts
         #[clap(long)]
+        #[serde(default)]
         mentions: Vec<String>,
         /// Transaction should not contain any of these accounts
         #[clap(long)]
+        #[serde(default)]
         exclude: Vec<String>,
         /// Transaction should contain all these accounts
         #[clap(long)]
+        #[serde(default)]
         required: Vec<String>,
     },
 }
 
-#[derive(Debug, Clone, Copy, ValueEnum)]
+#[derive(Debug, Clone, Copy, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 enum SubscribeUiAccountEncoding {
     Binary,
     Base58,
@@ -262,7 +368,8 @@ impl From<SubscribeUiAccountEncoding> for UiAccountEncoding {
     }
 }
 
-#[derive(Debug, Clone, Copy, ValueEnum)]
+#[derive(Debug, Clone, Copy, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 enum SubscribeUiTransactionEncoding {
     Binary,
     Base64,
@@ -283,7 +390,8 @@ impl From<SubscribeUiTransactionEncoding> for UiTransactionEncoding {
     }
 }
 
-#[derive(Debug, Clone, Copy, ValueEnum)]
+#[derive(Debug, Clone, Copy, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 enum SubscribeTransactionDetails {
     Full,
     Signatures,
@@ -302,74 +410,84 @@ impl From<SubscribeTransactionDetails> for TransactionDetails {
     }
 }
 
-#[allow(dead_code)]
+/// Named subscriptions for `subscribe --config`
 #[derive(Debug, Deserialize)]
-pub struct VersionWhirligigInfoVersion {
-    package: String,
-    version: String,
-    proto: String,
-    solana: String,
-    git: String,
-    rustc: String,
-    buildts: String,
+#[serde(deny_unknown_fields)]
+struct SubscribeConfig {
+    /// Number of connections, subscriptions are distributed round-robin
+    #[serde(default = "SubscribeConfig::default_connections")]
+    connections: usize,
+    subscriptions: Vec<SubscribeConfigEntry>,
 }
 
-#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
-struct VersionWhirligigInfoExtra {
-    #[serde(default)]
-    hostname: String,
+struct SubscribeConfigEntry {
+    name: String,
+    /// Overrides `--commitment`
+    commitment: Option<SubscribeCommitment>,
+    #[serde(flatten)]
+    action: SubscribeAction,
 }
 
-#[allow(dead_code)]
-#[derive(Debug, Deserialize)]
-struct VersionWhirligigInfo {
-    version: VersionWhirligigInfoVersion,
-    extra: VersionWhirligigInfoExtra,
-}
+impl SubscribeConfig {
+    const fn default_connections() -> usize {
+        1
+    }
 
-#[derive(Debug, Deserialize)]
-#[serde(untagged)]
-enum SubscribeResponse {
-    Subscribe(usize),
-    GetVersion(RpcVersionInfo),
-    GetVersionWhirligig(VersionWhirligigInfo),
+    fn load(path: &str) -> anyhow::Result<Self> {
+        let data =
+            fs::read_to_string(path).with_context(|| format!("failed to read config: {path}"))?;
+        let config: Self = match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&data)?,
+            Some("yaml" | "yml") => serde_yaml::from_str(&data)?,
+            _ => anyhow::bail!("unknown config format, expected toml or yaml: {path}"),
+        };
+
+        anyhow::ensure!(
+            config.connections > 0,
+            "at least one connection is required"
+        );
+        anyhow::ensure!(
+            !config.subscriptions.is_empty(),
+            "at least one subscription is required"
+        );
+        let mut names = HashSet::new();
+        for entry in config.subscriptions.iter() {
+            anyhow::ensure!(
+                names.insert(entry.name.as_str()),
+                "subscription name is not unique: {}",
+                entry.name
+            );
+        }
+
+        Ok(config)
+    }
 }
 
-async fn subscribe_experimental(
-    endpoint: &str,
-    method: &str,
-    params: Value,
-) -> anyhow::Result<(
-    SubscribeResponse,
-    WebSocketStream<MaybeTlsStream<TcpStream>>,
-)> {
-    let (mut stream, _) = connect_async(endpoint).await?;
-    stream
-        .send(Message::Text(
-            json!({
-                "jsonrpc": "2.0",
-                "id": 1,
-                "method": method,
-                "params": params,
-            })
-            .to_string(),
-        ))
-        .await?;
-
-    match stream.next().await {
-        Some(Ok(Message::Text(data))) => {
-            let response = match serde_json::from_str(&data)? {
-                RpcOutput::Success(output) => serde_json::from_value(output.result)?,
-                RpcOutput::Failure(failure) => {
-                    anyhow::bail!("subscribe error: {:?}", failure.error)
+async fn process_stream<T: fmt::Debug + Serialize>(
+    mut stream: Subscription<T>,
+    name: Option<&str>,
+    kind: &str,
+    output: &Output,
+) -> anyhow::Result<()> {
+    let ctrl_c = signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    loop {
+        tokio::select! {
+            item = stream.next() => match item {
+                Some(item) => output.on_new_item(name, kind, &item?)?,
+                None => return Ok(()),
+            },
+            result = &mut ctrl_c => {
+                result?;
+                stream.unsubscribe().await?;
+                match name {
+                    Some(name) => info!("{name} ({kind}), unsubscribed"),
+                    None => info!("{kind}, unsubscribed"),
                 }
-            };
-            Ok((response, stream))
+                return Ok(());
+            }
         }
-        Some(Ok(_)) => anyhow::bail!("invalid message type"),
-        Some(Err(error)) => anyhow::bail!(error),
-        None => anyhow::bail!("no messages"),
     }
 }
 
@@ -378,281 +496,364 @@ async fn main() -> anyhow::Result<()> {
     whirligig_client::tracer_init()?;
 
     let args = Args::parse();
-    let client = PubsubClient::new(&args.endpoint).await.unwrap();
+    let config = match &args.action {
+        ArgsAction::Subscribe { action, config, .. } => match (action, config) {
+            (Some(_), None) => None,
+            (None, Some(path)) => Some(SubscribeConfig::load(path)?),
+            _ => anyhow::bail!("either subscription type or `--config` should be provided"),
+        },
+        _ => None,
+    };
+    let (capture, recorder) = match &args.action {
+        ArgsAction::Subscribe {
+            record: Some(path),
+            record_zstd,
+            ..
+        } => {
+            let (capture, recorder) = spawn_recorder(path, *record_zstd)?;
+            (Some(capture), Some(recorder))
+        }
+        _ => (None, None),
+    };
+    let mut clients = vec![];
+    for _ in 0..config.as_ref().map_or(1, |config| config.connections) {
+        clients.push(connect(&args, capture.clone()).await?);
+    }
+    drop(capture);
+
+    let result = run_action(&clients, args.action, config).await;
+    // connection is closed once client and subscriptions are dropped,
+    // recorder finishes after that
+    drop(clients);
+    if let Some(recorder) = recorder {
+        recorder.await??;
+    }
+    result
+}
+
+async fn connect(
+    args: &Args,
+    capture: Option<mpsc::UnboundedSender<CaptureRecord>>,
+) -> anyhow::Result<WhirligigClient> {
+    let client = WhirligigClient::connect_with_config(
+        &args.endpoint,
+        ClientConfig {
+            ping_interval: args.reconnect.then_some(Duration::from_secs(10)),
+            reconnect: args.reconnect.then(ReconnectConfig::default),
+            capture,
+            ..Default::default()
+        },
+    )
+    .await?;
+    if args.reconnect {
+        let mut events = client.subscribe_events();
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => warn!("connection event: {event:?}"),
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    Ok(client)
+}
 
-    match args.action {
+fn spawn_recorder(
+    path: &str,
+    zstd: bool,
+) -> anyhow::Result<(
+    mpsc::UnboundedSender<CaptureRecord>,
+    JoinHandle<anyhow::Result<()>>,
+)> {
+    let mut writer = CaptureWriter::open(path, zstd)
+        .with_context(|| format!("failed to open capture file: {path}"))?;
+    let (capture_tx, capture_rx) = mpsc::unbounded();
+    let recorder = tokio::task::spawn_blocking(move || {
+        for record in block_on_stream(capture_rx) {
+            writer.write(&record)?;
+        }
+        writer.flush()?;
+        Ok(())
+    });
+    Ok((capture_tx, recorder))
+}
+
+async fn run_action(
+    clients: &[WhirligigClient],
+    action: ArgsAction,
+    config: Option<SubscribeConfig>,
+) -> anyhow::Result<()> {
+    let client = &clients[0];
+    match action {
         ArgsAction::Subscribe {
             action,
+            config: _,
             commitment,
             only_counter,
+            output,
+            record: _,
+            record_zstd: _,
         } => {
-            let pb = only_counter
-                .then(|| {
-                    let pb = ProgressBar::new(u64::MAX);
-                    pb.set_style(ProgressStyle::with_template(
-                        "{spinner:.green} +{pos} messages",
-                    )?);
-                    Ok::<_, anyhow::Error>(pb)
-                })
-                .transpose()?;
-            let on_new_item = |f: &dyn Fn()| {
-                if let Some(pb) = &pb {
-                    pb.inc(1)
-                } else {
-                    f();
-                }
-            };
-
-            match action {
-                // STABLE
-                SubscribeAction::Account {
-                    pubkey,
-                    encoding,
-                    data_slice,
-                } => {
-                    let pubkey = Pubkey::from_str(&pubkey)
-                        .with_context(|| format!("invalid pubkey: {pubkey}"))?;
-
-                    let (mut stream, _unsubscribe) = client
-                        .account_subscribe(
-                            &pubkey,
-                            Some(RpcAccountInfoConfig {
-                                encoding: encoding.map(Into::into),
-                                data_slice: Args::parse_data_slice(&data_slice)?,
-                                commitment: Some(commitment.into()),
-                                min_context_slot: None,
-                            }),
-                        )
-                        .await?;
-                    while let Some(item) = stream.next().await {
-                        on_new_item(&|| info!("account, new item: {item:?}"));
-                    }
-                }
-                SubscribeAction::Logs {
-                    all,
-                    all_with_votes,
-                    mentions,
-                } => {
-                    let filter = match (all, all_with_votes, !mentions.is_empty()) {
-                        (true, false, false) => RpcTransactionLogsFilter::All,
-                        (false, true, false) => RpcTransactionLogsFilter::AllWithVotes,
-                        (false, false, true) => RpcTransactionLogsFilter::Mentions(mentions),
-                        _ => anyhow::bail!(
-                            "conflicts between `all`, `all-with-votes` and `mentions`"
-                        ),
-                    };
-
-                    let (mut stream, _unsubscribe) = client
-                        .logs_subscribe(
-                            filter,
-                            RpcTransactionLogsConfig {
-                                commitment: Some(commitment.into()),
-                            },
-                        )
-                        .await?;
-                    while let Some(item) = stream.next().await {
-                        on_new_item(&|| info!("logs, new item: {item:?}"));
-                    }
-                }
-                SubscribeAction::Program {
-                    pubkey,
-                    filter_data_size,
-                    filter_memcmp,
-                    encoding,
-                    data_slice,
-                } => {
-                    let pubkey = Pubkey::from_str(&pubkey)
-                        .with_context(|| format!("invalid pubkey: {pubkey}"))?;
-
-                    let mut filters = vec![];
-                    for data_size in filter_data_size {
-                        filters.push(RpcFilterType::DataSize(data_size));
-                    }
-                    for memcmp in filter_memcmp {
-                        match memcmp.split_once(',') {
-                            Some((offset, data)) => {
-                                filters.push(RpcFilterType::Memcmp(Memcmp::new(
-                                    offset.parse().with_context(|| {
-                                        format!("invalid offset in memcmp: {offset}")
-                                    })?,
-                                    MemcmpEncodedBytes::Base58(data.to_owned()),
-                                )))
+            let output = Output::new(output, only_counter)?;
+
+            if let Some(config) = config {
+                let output = &output;
+                try_join_all(
+                    config
+                        .subscriptions
+                        .into_iter()
+                        .zip(clients.iter().cycle())
+                        .map(|(entry, client)| {
+                            let SubscribeConfigEntry {
+                                name,
+                                commitment: entry_commitment,
+                                action,
+                            } = entry;
+                            let commitment = entry_commitment.unwrap_or(commitment);
+                            async move {
+                                subscribe(client, action, commitment, Some(&name), output).await
                             }
-                            _ => anyhow::bail!("invalid memcmp: {memcmp}"),
-                        }
-                    }
-
-                    let (mut stream, _unsubscribe) = client
-                        .program_subscribe(
-                            &pubkey,
-                            Some(RpcProgramAccountsConfig {
-                                filters: Some(filters),
-                                account_config: RpcAccountInfoConfig {
-                                    encoding: encoding.map(|e| e.into()),
-                                    data_slice: Args::parse_data_slice(&data_slice)?,
-                                    commitment: Some(commitment.into()),
-                                    min_context_slot: None,
-                                },
-                                with_context: None,
-                            }),
-                        )
-                        .await?;
-                    while let Some(item) = stream.next().await {
-                        on_new_item(&|| info!("program, new item: {item:?}"));
-                    }
-                }
-                SubscribeAction::Signature { signature } => {
-                    let signature = Signature::from_str(&signature)
-                        .with_context(|| format!("invalid signature: {signature}"))?;
-
-                    let (mut stream, _unsubscribe) = client
-                        .signature_subscribe(
-                            &signature,
-                            Some(RpcSignatureSubscribeConfig {
-                                commitment: Some(commitment.into()),
-                                enable_received_notification: None,
-                            }),
-                        )
-                        .await?;
-                    while let Some(item) = stream.next().await {
-                        on_new_item(&|| info!("signature, new item: {item:?}"));
-                    }
-                }
-                SubscribeAction::Slot => {
-                    let (mut stream, _unsubscribe) = client.slot_subscribe().await?;
-                    while let Some(item) = stream.next().await {
-                        on_new_item(&|| info!("slot, new item: {item:?}"));
-                    }
-                }
-                // UNSTABLE
-                SubscribeAction::Block {
-                    pubkey,
-                    encoding,
-                    transaction_details,
-                    show_rewards,
-                    max_supported_transaction_version,
-                } => {
-                    let filter = if let Some(pubkey) = pubkey {
-                        RpcBlockSubscribeFilter::MentionsAccountOrProgram(
-                            Pubkey::from_str(&pubkey)
-                                .with_context(|| format!("invalid pubkey: {pubkey}"))?
-                                .to_string(),
-                        )
-                    } else {
-                        RpcBlockSubscribeFilter::All
-                    };
-
-                    let (mut stream, _unsubscribe) = client
-                        .block_subscribe(
-                            filter,
-                            Some(RpcBlockSubscribeConfig {
-                                commitment: Some(commitment.into()),
-                                encoding: encoding.map(Into::into),
-                                transaction_details: transaction_details.map(Into::into),
-                                show_rewards,
-                                max_supported_transaction_version,
-                            }),
-                        )
-                        .await?;
-                    while let Some(item) = stream.next().await {
-                        on_new_item(&|| info!("block, new item: {item:?}"));
-                    }
-                }
-                // EXPERIMENTAL
-                SubscribeAction::Transaction {
-                    vote,
-                    failed,
-                    signature,
-                    account_include,
-                    account_exclude,
-                    account_required,
-                    encoding,
-                    transaction_details,
-                    show_rewards,
-                    max_supported_transaction_version,
-                } => {
-                    let (_response, mut stream) = subscribe_experimental(
-                        &args.endpoint,
-                        "transactionSubscribe",
-                        json!([{
-                            "vote": vote,
-                            "failed": failed,
-                            "signature": signature,
-                            "accounts": {
-                                "include": account_include,
-                                "exclude": account_exclude,
-                                "required": account_required,
-                            }
-                        }, {
-                            "commitment": CommitmentConfig::from(commitment),
-                            "encoding": encoding.map(UiTransactionEncoding::from),
-                            "transactionDetails": transaction_details.map(TransactionDetails::from),
-                            "showRewards": show_rewards,
-                            "maxSupportedTransactionVersion": max_supported_transaction_version,
-                        }]),
-                    )
-                    .await?;
-                    while let Some(item) = stream.next().await {
-                        on_new_item(&|| info!("transaction, new item: {item:?}"));
-                    }
-                }
-                SubscribeAction::TransactionDeprecated {
-                    vote,
-                    failed,
-                    mentions,
-                    exclude,
-                    required,
-                } => {
-                    let (_response, mut stream) = subscribe_experimental(
-                        &args.endpoint,
-                        "transactionSubscribe",
-                        json!([{
-                            "vote": vote,
-                            "failed": failed,
-                            "include": mentions,
-                            "exclude": exclude,
-                            "required": required,
-                        }, {
-                            "commitment": CommitmentConfig::from(commitment),
-                        }]),
-                    )
-                    .await?;
-                    while let Some(item) = stream.next().await {
-                        on_new_item(&|| info!("transaction, new item: {item:?}"));
-                    }
-                }
+                        }),
+                )
+                .await?;
+            } else {
+                let action = action.context("subscription type is required")?;
+                subscribe(client, action, commitment, None, &output).await?;
             }
         }
         ArgsAction::GetVersion => {
-            let (response, _stream) =
-                subscribe_experimental(&args.endpoint, "getVersion", json!([])).await?;
-            match response {
-                SubscribeResponse::Subscribe(_id) => {
-                    unreachable!("invalid response")
-                }
-                SubscribeResponse::GetVersion(info) => info!(
-                    "solana_core: {}, feature_set: {:?}",
-                    info.solana_core, info.feature_set
-                ),
-                SubscribeResponse::GetVersionWhirligig(_info) => {
-                    unreachable!("invalid response")
-                }
-            }
+            let info = client.get_version().await?;
+            info!(
+                "solana_core: {}, feature_set: {:?}",
+                info.solana_core, info.feature_set
+            );
         }
         ArgsAction::GetVersionWhirligig => {
-            let (response, _stream) =
-                subscribe_experimental(&args.endpoint, "getVersionWhirligig", json!([])).await?;
-            match response {
-                SubscribeResponse::Subscribe(_id) => {
-                    unreachable!("invalid response")
-                }
-                SubscribeResponse::GetVersion(_info) => {
-                    unreachable!("invalid response")
-                }
-                SubscribeResponse::GetVersionWhirligig(info) => {
-                    info!("whirligig version: {:#?}", info)
+            let info = client.get_version_whirligig().await?;
+            info!("whirligig version: {:#?}", info);
+        }
+    }
+
+    Ok(())
+}
+
+async fn subscribe(
+    client: &WhirligigClient,
+    action: SubscribeAction,
+    commitment: SubscribeCommitment,
+    name: Option<&str>,
+    output: &Output,
+) -> anyhow::Result<()> {
+    match action {
+        // STABLE
+        SubscribeAction::Account {
+            pubkey,
+            encoding,
+            data_slice,
+        } => {
+            let pubkey =
+                Pubkey::from_str(&pubkey).with_context(|| format!("invalid pubkey: {pubkey}"))?;
+
+            let stream = client
+                .account_subscribe(
+                    &pubkey,
+                    Some(RpcAccountInfoConfig {
+                        encoding: encoding.map(Into::into),
+                        data_slice: Args::parse_data_slice(&data_slice)?,
+                        commitment: Some(commitment.into()),
+                        min_context_slot: None,
+                    }),
+                )
+                .await?;
+            process_stream(stream, name, "account", output).await?;
+        }
+        SubscribeAction::Logs {
+            all,
+            all_with_votes,
+            mentions,
+        } => {
+            let filter = match (all, all_with_votes, !mentions.is_empty()) {
+                (true, false, false) => RpcTransactionLogsFilter::All,
+                (false, true, false) => RpcTransactionLogsFilter::AllWithVotes,
+                (false, false, true) => RpcTransactionLogsFilter::Mentions(mentions),
+                _ => anyhow::bail!("conflicts between `all`, `all-with-votes` and `mentions`"),
+            };
+
+            let stream = client
+                .logs_subscribe(
+                    filter,
+                    RpcTransactionLogsConfig {
+                        commitment: Some(commitment.into()),
+                    },
+                )
+                .await?;
+            process_stream(stream, name, "logs", output).await?;
+        }
+        SubscribeAction::Program {
+            pubkey,
+            filter_data_size,
+            filter_memcmp,
+            encoding,
+            data_slice,
+        } => {
+            let pubkey =
+                Pubkey::from_str(&pubkey).with_context(|| format!("invalid pubkey: {pubkey}"))?;
+
+            let mut filters = vec![];
+            for data_size in filter_data_size {
+                filters.push(RpcFilterType::DataSize(data_size));
+            }
+            for memcmp in filter_memcmp {
+                match memcmp.split_once(',') {
+                    Some((offset, data)) => filters.push(RpcFilterType::Memcmp(Memcmp::new(
+                        offset
+                            .parse()
+                            .with_context(|| format!("invalid offset in memcmp: {offset}"))?,
+                        MemcmpEncodedBytes::Base58(data.to_owned()),
+                    ))),
+                    _ => anyhow::bail!("invalid memcmp: {memcmp}"),
                 }
             }
+
+            let stream = client
+                .program_subscribe(
+                    &pubkey,
+                    Some(RpcProgramAccountsConfig {
+                        filters: Some(filters),
+                        account_config: RpcAccountInfoConfig {
+                            encoding: encoding.map(|e| e.into()),
+                            data_slice: Args::parse_data_slice(&data_slice)?,
+                            commitment: Some(commitment.into()),
+                            min_context_slot: None,
+                        },
+                        with_context: None,
+                    }),
+                )
+                .await?;
+            process_stream(stream, name, "program", output).await?;
+        }
+        SubscribeAction::Signature { signature } => {
+            let signature = Signature::from_str(&signature)
+                .with_context(|| format!("invalid signature: {signature}"))?;
+
+            let stream = client
+                .signature_subscribe(
+                    &signature,
+                    Some(RpcSignatureSubscribeConfig {
+                        commitment: Some(commitment.into()),
+                        enable_received_notification: None,
+                    }),
+                )
+                .await?;
+            process_stream(stream, name, "signature", output).await?;
+        }
+        SubscribeAction::Slot {} => {
+            let stream = client.slot_subscribe().await?;
+            process_stream(stream, name, "slot", output).await?;
+        }
+        // UNSTABLE
+        SubscribeAction::Block {
+            pubkey,
+            encoding,
+            transaction_details,
+            show_rewards,
+            max_supported_transaction_version,
+        } => {
+            let filter = if let Some(pubkey) = pubkey {
+                RpcBlockSubscribeFilter::MentionsAccountOrProgram(
+                    Pubkey::from_str(&pubkey)
+                        .with_context(|| format!("invalid pubkey: {pubkey}"))?
+                        .to_string(),
+                )
+            } else {
+                RpcBlockSubscribeFilter::All
+            };
+
+            let stream = client
+                .block_subscribe(
+                    filter,
+                    Some(RpcBlockSubscribeConfig {
+                        commitment: Some(commitment.into()),
+                        encoding: encoding.map(Into::into),
+                        transaction_details: transaction_details.map(Into::into),
+                        show_rewards,
+                        max_supported_transaction_version,
+                    }),
+                )
+                .await?;
+            process_stream(stream, name, "block", output).await?;
+        }
+        SubscribeAction::Root {} => {
+            let stream = client.root_subscribe().await?;
+            process_stream(stream, name, "root", output).await?;
+        }
+        SubscribeAction::SlotUpdate {} => {
+            let stream = client.slots_updates_subscribe().await?;
+            process_stream(stream, name, "slot-update", output).await?;
+        }
+        SubscribeAction::Vote {} => {
+            let stream = client.vote_subscribe().await?;
+            process_stream(stream, name, "vote", output).await?;
+        }
+        // EXPERIMENTAL
+        SubscribeAction::Transaction {
+            vote,
+            failed,
+            signature,
+            account_include,
+            account_exclude,
+            account_required,
+            encoding,
+            transaction_details,
+            show_rewards,
+            max_supported_transaction_version,
+        } => {
+            let stream = client
+                .transaction_subscribe(
+                    TransactionSubscribeFilter {
+                        vote,
+                        failed,
+                        signature,
+                        accounts: TransactionSubscribeFilterAccounts {
+                            include: account_include,
+                            exclude: account_exclude,
+                            required: account_required,
+                        },
+                    },
+                    TransactionSubscribeConfig {
+                        commitment: Some(commitment.into()),
+                        encoding: encoding.map(Into::into),
+                        transaction_details: transaction_details.map(Into::into),
+                        show_rewards,
+                        max_supported_transaction_version,
+                    },
+                )
+                .await?;
+            process_stream(stream, name, "transaction", output).await?;
+        }
+        SubscribeAction::TransactionDeprecated {
+            vote,
+            failed,
+            mentions,
+            exclude,
+            required,
+        } => {
+            let stream = client
+                .transaction_subscribe_deprecated(
+                    TransactionSubscribeFilterDeprecated {
+                        vote,
+                        failed,
+                        include: mentions,
+                        exclude,
+                        required,
+                    },
+                    commitment.into(),
+                )
+                .await?;
+            process_stream(stream, name, "transaction", output).await?;
         }
     }
 
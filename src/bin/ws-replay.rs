@@ -0,0 +1,348 @@
+use {
+    anyhow::Context,
+    clap::Parser,
+    futures::{
+        channel::mpsc,
+        future::{AbortHandle, Abortable},
+        sink::SinkExt,
+        stream::StreamExt,
+    },
+    serde::Deserialize,
+    serde_json::{json, Value},
+    std::{
+        collections::{HashMap, HashSet},
+        net::SocketAddr,
+        sync::Arc,
+    },
+    tokio::{
+        net::{TcpListener, TcpStream},
+        time::{sleep_until, Duration, Instant},
+    },
+    tokio_tungstenite::{accept_async, tungstenite::protocol::Message},
+    tracing::{info, warn},
+    whirligig_client::capture::{CaptureFrame, CaptureReader},
+};
+
+#[derive(Debug, Clone, Parser)]
+struct Args {
+    /// Path to capture recorded with `ws-client subscribe --record`
+    #[clap(short, long)]
+    capture: String,
+
+    /// Address for WebSocket server
+    #[clap(short, long, default_value_t = String::from("127.0.0.1:8000"))]
+    listen: String,
+
+    /// Replay speed multiplier, `2` replays twice as fast as recorded
+    #[clap(long, default_value_t = 1.0, conflicts_with = "fast")]
+    speed: f64,
+
+    /// Replay notifications as fast as possible, ignoring recorded pace
+    #[clap(long, default_value_t = false, conflicts_with = "speed")]
+    fast: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecordedNotification {
+    method: String,
+    params: RecordedNotificationParams,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecordedNotificationParams {
+    result: Value,
+    subscription: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// Recorded subscription, `params` are known only if subscription records
+/// are in the capture
+#[derive(Debug)]
+struct RecordedSubscription {
+    params: Option<Value>,
+    notifications: Vec<(Duration, Value)>,
+}
+
+/// Notifications of recorded subscriptions grouped by notification method,
+/// subscriptions are in order of subscribe responses or of the first
+/// notification for captures without subscription records. Offsets are
+/// continuous across sessions of the capture: monotonic time restarts in
+/// every session and the gap between sessions is skipped
+#[derive(Debug, Default)]
+struct Capture {
+    subscriptions: HashMap<String, Vec<RecordedSubscription>>,
+}
+
+impl Capture {
+    fn load(path: &str) -> anyhow::Result<Self> {
+        let mut capture = Self::default();
+        // server side ids are unique only within one session
+        let mut ids = HashMap::<(u32, String, u64), usize>::new();
+        // client side ids stay the same when subscription is re-issued after
+        // reconnect, but are unique only within one connection, so params are
+        // part of the key
+        let mut client_ids = HashMap::<(u32, String, u64, String), usize>::new();
+        // session index, its first monotonic time and offset of its start
+        let mut session = None::<(u32, Duration, Duration)>;
+        let mut last_offset = Duration::ZERO;
+
+        let reader =
+            CaptureReader::open(path).with_context(|| format!("failed to open capture: {path}"))?;
+        for record in reader {
+            let record = record?;
+            let data = match record.frame {
+                CaptureFrame::Text(data) => data,
+                CaptureFrame::Binary(_) => continue,
+                CaptureFrame::Subscription(subscription) => {
+                    let notification = notification_method(&subscription.method);
+                    let subscriptions = capture
+                        .subscriptions
+                        .entry(notification.clone())
+                        .or_default();
+                    let index = *client_ids
+                        .entry((
+                            record.session,
+                            notification.clone(),
+                            subscription.id,
+                            subscription.params.to_string(),
+                        ))
+                        .or_insert_with(|| {
+                            subscriptions.push(RecordedSubscription {
+                                params: Some(subscription.params),
+                                notifications: vec![],
+                            });
+                            subscriptions.len() - 1
+                        });
+                    ids.insert(
+                        (record.session, notification, subscription.subscription),
+                        index,
+                    );
+                    continue;
+                }
+            };
+            let Ok(notification) = serde_json::from_str::<RecordedNotification>(&data) else {
+                continue;
+            };
+
+            let (start, base) = match session {
+                Some((index, start, base)) if index == record.session => (start, base),
+                _ => {
+                    session = Some((record.session, record.monotonic, last_offset));
+                    (record.monotonic, last_offset)
+                }
+            };
+            let offset = base + record.monotonic.saturating_sub(start);
+            last_offset = offset;
+            let subscriptions = capture
+                .subscriptions
+                .entry(notification.method.clone())
+                .or_default();
+            let index = *ids
+                .entry((
+                    record.session,
+                    notification.method,
+                    notification.params.subscription,
+                ))
+                .or_insert_with(|| {
+                    subscriptions.push(RecordedSubscription {
+                        params: None,
+                        notifications: vec![],
+                    });
+                    subscriptions.len() - 1
+                });
+            subscriptions[index]
+                .notifications
+                .push((offset, notification.params.result));
+        }
+
+        Ok(capture)
+    }
+
+    /// Notifications of the first recorded subscription not replayed on the
+    /// connection yet, matched by params if subscription records are in the
+    /// capture, otherwise by subscribe order
+    fn find(
+        &self,
+        notification: &str,
+        params: &Value,
+        replayed: &mut HashSet<(String, usize)>,
+    ) -> Vec<(Duration, Value)> {
+        let Some(subscriptions) = self.subscriptions.get(notification) else {
+            return vec![];
+        };
+        let by_params = subscriptions
+            .iter()
+            .any(|subscription| subscription.params.is_some());
+        subscriptions
+            .iter()
+            .enumerate()
+            .find(|(index, subscription)| {
+                (!by_params || subscription.params.as_ref() == Some(params))
+                    && !replayed.contains(&(notification.to_owned(), *index))
+            })
+            .map(|(index, subscription)| {
+                replayed.insert((notification.to_owned(), index));
+                subscription.notifications.clone()
+            })
+            .unwrap_or_default()
+    }
+}
+
+fn notification_method(method: &str) -> String {
+    format!("{}Notification", method.trim_end_matches("Subscribe"))
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    whirligig_client::tracer_init()?;
+
+    let args = Args::parse();
+    anyhow::ensure!(args.speed > 0.0, "speed should be positive");
+
+    let capture = Arc::new(Capture::load(&args.capture)?);
+    for (method, subscriptions) in capture.subscriptions.iter() {
+        let total = subscriptions
+            .iter()
+            .map(|subscription| subscription.notifications.len())
+            .sum::<usize>();
+        info!(
+            "loaded {method}: {} subscriptions, {total} notifications",
+            subscriptions.len()
+        );
+    }
+
+    let listener = TcpListener::bind(&args.listen).await?;
+    info!("listen on {}", listener.local_addr()?);
+    loop {
+        let (stream, addr) = listener.accept().await?;
+        let capture = Arc::clone(&capture);
+        let args = args.clone();
+        tokio::spawn(async move {
+            if let Err(error) = handle_connection(stream, addr, capture, args).await {
+                warn!("{addr}: connection error: {error}");
+            }
+        });
+    }
+}
+
+async fn handle_connection(
+    stream: TcpStream,
+    addr: SocketAddr,
+    capture: Arc<Capture>,
+    args: Args,
+) -> anyhow::Result<()> {
+    let (mut write, mut read) = accept_async(stream).await?.split();
+    info!("{addr}: new connection");
+
+    let (messages_tx, mut messages_rx) = mpsc::unbounded::<Value>();
+    let mut next_subscription_id = 0u64;
+    let mut replayed = HashSet::<(String, usize)>::new();
+    let mut replays = HashMap::<u64, AbortHandle>::new();
+
+    loop {
+        tokio::select! {
+            message = read.next() => {
+                let data = match message {
+                    Some(Ok(Message::Text(data))) => data,
+                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Ok(_)) => continue,
+                    Some(Err(error)) => return Err(error.into()),
+                };
+                let request = match serde_json::from_str::<Request>(&data) {
+                    Ok(request) => request,
+                    Err(error) => {
+                        warn!("{addr}: invalid request ({error}): {data}");
+                        continue;
+                    }
+                };
+
+                let mut response = if let Some(kind) = request.method.strip_suffix("Unsubscribe") {
+                    let id = request
+                        .params
+                        .get(0)
+                        .and_then(Value::as_u64)
+                        .unwrap_or_default();
+                    let replay = replays.remove(&id);
+                    info!("{addr}: {kind}Unsubscribe #{id}");
+                    json!({ "result": replay.map(|replay| replay.abort()).is_some() })
+                } else if request.method.ends_with("Subscribe") {
+                    let notification = notification_method(&request.method);
+                    let notifications =
+                        capture.find(&notification, &request.params, &mut replayed);
+
+                    next_subscription_id += 1;
+                    let id = next_subscription_id;
+                    info!(
+                        "{addr}: {}, #{id} with {} notifications",
+                        request.method,
+                        notifications.len()
+                    );
+
+                    let (abort_handle, abort_registration) = AbortHandle::new_pair();
+                    tokio::spawn(Abortable::new(
+                        replay(
+                            notification,
+                            id,
+                            notifications,
+                            messages_tx.clone(),
+                            args.speed,
+                            args.fast,
+                        ),
+                        abort_registration,
+                    ));
+                    replays.insert(id, abort_handle);
+                    json!({ "result": id })
+                } else {
+                    json!({ "error": { "code": -32601, "message": "Method not found" } })
+                };
+
+                response["jsonrpc"] = json!("2.0");
+                response["id"] = request.id;
+                write.send(Message::Text(response.to_string())).await?;
+            }
+            Some(message) = messages_rx.next() => {
+                write.send(Message::Text(message.to_string())).await?;
+            }
+        }
+    }
+
+    for replay in replays.into_values() {
+        replay.abort();
+    }
+    info!("{addr}: connection closed");
+    Ok(())
+}
+
+async fn replay(
+    method: String,
+    id: u64,
+    notifications: Vec<(Duration, Value)>,
+    messages_tx: mpsc::UnboundedSender<Value>,
+    speed: f64,
+    fast: bool,
+) {
+    let start = Instant::now();
+    for (offset, result) in notifications {
+        if !fast {
+            sleep_until(start + offset.div_f64(speed)).await;
+        }
+        let message = json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": {
+                "result": result,
+                "subscription": id,
+            }
+        });
+        if messages_tx.unbounded_send(message).is_err() {
+            break;
+        }
+    }
+}
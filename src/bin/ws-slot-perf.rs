@@ -1,107 +1,1663 @@
 use {
-    clap::Parser,
-    futures::{sink::SinkExt, stream::StreamExt},
+    anyhow::Context,
+    clap::{Parser, Subcommand, ValueEnum},
+    futures::{
+        future::{self, BoxFuture, FutureExt},
+        sink::SinkExt,
+        stream::{self, BoxStream, StreamExt},
+    },
+    hdrhistogram::{
+        serialization::{
+            interval_log::{IntervalLogWriterBuilder, Tag},
+            V2DeflateSerializer,
+        },
+        Histogram,
+    },
     maplit::hashmap,
-    solana_client::{nonblocking::pubsub_client::PubsubClient, rpc_response::SlotInfo},
-    std::{collections::BTreeMap, time::Instant},
+    solana_account_decoder::{UiAccount, UiAccountEncoding},
+    solana_client::{
+        nonblocking::pubsub_client::PubsubClient,
+        rpc_config::{
+            RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcTransactionLogsConfig,
+            RpcTransactionLogsFilter,
+        },
+        rpc_response::{SlotInfo, SlotUpdate},
+    },
+    solana_sdk::{
+        account::Account,
+        clock::Slot,
+        commitment_config::CommitmentConfig,
+        hash::{hashv, Hash},
+        pubkey::Pubkey,
+        signature::Signature,
+    },
+    solana_transaction_status::TransactionDetails,
+    std::{
+        collections::{btree_map::Entry, BTreeMap, BTreeSet},
+        fs::File,
+        io::{BufWriter, Write},
+        pin::Pin,
+        str::FromStr,
+        time::{Duration, Instant, SystemTime},
+    },
+    tokio::{
+        signal,
+        sync::{mpsc, oneshot},
+        task::JoinSet,
+        time::{self, Interval, MissedTickBehavior, Sleep},
+    },
+    whirligig_client::{
+        client::WhirligigClient,
+        types::{
+            TransactionNotification, TransactionSubscribeConfig, TransactionSubscribeFilter,
+            TransactionSubscribeFilterAccounts,
+        },
+    },
     yellowstone_grpc_client::GeyserGrpcClient,
     yellowstone_grpc_proto::prelude::{
         subscribe_update::UpdateOneof, CommitmentLevel, SubscribeRequest,
-        SubscribeRequestFilterSlots, SubscribeUpdate, SubscribeUpdateSlot,
+        SubscribeRequestFilterAccounts, SubscribeRequestFilterSlots,
+        SubscribeRequestFilterTransactions, SubscribeUpdate, SubscribeUpdateAccount,
+        SubscribeUpdateAccountInfo, SubscribeUpdateSlot, SubscribeUpdateTransaction,
+        SubscribeUpdateTransactionInfo,
     },
 };
 
 #[derive(Debug, Clone, Parser)]
 struct Args {
-    /// Solana PubSubWebSocket endpoint
+    /// Solana PubSubWebSocket endpoint, `[LABEL=]URL`, can be repeated
     #[clap(long)]
-    pubsub: String,
+    pubsub: Vec<String>,
 
-    /// Triton Whirligig WebSocket endpoint
+    /// Triton Whirligig WebSocket endpoint, `[LABEL=]URL`, can be repeated
     #[clap(long)]
-    whirligig: String,
+    whirligig: Vec<String>,
 
-    /// Triton gRPC endpoint
+    /// Triton gRPC endpoint, `[LABEL=]URL`, can be repeated
     #[clap(long)]
-    grpc: String,
+    grpc: Vec<String>,
 
-    /// Triton gRPC endpoint x-token
+    /// Triton gRPC endpoint x-token, used for all gRPC endpoints
     #[clap(long)]
     x_token: Option<String>,
+
+    /// Stop after duration, e.g. `10m`
+    #[clap(long, value_parser = humantime::parse_duration)]
+    duration: Option<Duration>,
+
+    /// Stop after number of slots since the first compared item
+    #[clap(long)]
+    slots: Option<u64>,
+
+    /// Number of slots to track not completed items, older items are evicted
+    /// and reported as missed
+    #[clap(long, default_value_t = 64)]
+    retention: u64,
+
+    /// Warn when source did not deliver anything for duration while other
+    /// sources did
+    #[clap(long, value_parser = humantime::parse_duration, default_value = "5s")]
+    stall_timeout: Duration,
+
+    /// Print summary periodically
+    #[clap(long, value_parser = humantime::parse_duration, default_value = "1m")]
+    summary_interval: Duration,
+
+    /// Save per-source delay histograms as HDR interval log on exit
+    #[clap(long)]
+    hdr_dump: Option<String>,
+
+    /// What to compare, slots by default
+    #[command(subcommand)]
+    action: Option<ArgsAction>,
+}
+
+impl Args {
+    /// Sources in the order of arguments: PubSub, Whirligig, gRPC, label is
+    /// source kind by default or kind with index if kind is repeated
+    fn sources(&self) -> anyhow::Result<Vec<SourceEndpoint>> {
+        let mut sources = Vec::<SourceEndpoint>::new();
+        for (kind, endpoints) in [
+            (SourceKind::PubSub, &self.pubsub),
+            (SourceKind::Whirligig, &self.whirligig),
+            (SourceKind::Grpc, &self.grpc),
+        ] {
+            for (index, endpoint) in endpoints.iter().enumerate() {
+                let (label, endpoint) = match endpoint.split_once('=') {
+                    Some((label, endpoint)) if !label.contains([':', '/']) => {
+                        (label.to_owned(), endpoint.to_owned())
+                    }
+                    _ if endpoints.len() == 1 => (kind.name().to_owned(), endpoint.clone()),
+                    _ => (format!("{}-{}", kind.name(), index + 1), endpoint.clone()),
+                };
+                anyhow::ensure!(
+                    !label.is_empty()
+                        && label
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')),
+                    "invalid source label: {label}"
+                );
+                anyhow::ensure!(
+                    sources.iter().all(|source| source.label != label),
+                    "duplicate source label: {label}"
+                );
+                sources.push(SourceEndpoint {
+                    kind,
+                    label,
+                    endpoint,
+                });
+            }
+        }
+        anyhow::ensure!(
+            !sources.is_empty(),
+            "at least one of --pubsub, --whirligig or --grpc is required"
+        );
+        Ok(sources)
+    }
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum ArgsAction {
+    /// Compare arrival of slot notifications
+    Slot {
+        /// Slot stage to compare
+        #[clap(long, value_enum, default_value_t = SlotStage::default())]
+        commitment: SlotStage,
+    },
+    /// Compare arrival of account updates per (pubkey, slot, write_version)
+    Account {
+        /// Account key, can be repeated
+        #[clap(long, required_unless_present = "program")]
+        pubkey: Vec<String>,
+        /// Compare updates of all accounts owned by program
+        #[clap(long, conflicts_with = "pubkey")]
+        program: Option<String>,
+    },
+    /// Compare arrival of transactions per signature
+    Transaction {
+        /// Transaction should mention account, can be repeated
+        #[clap(long, required = true)]
+        account: Vec<String>,
+    },
+}
+
+/// Slot stage, in the order of the slot lifecycle
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum SlotStage {
+    /// First shred received, not supported by gRPC
+    FirstShred,
+    /// `slotSubscribe` for WebSocket sources
+    #[default]
+    Processed,
+    /// Bank frozen, not supported by gRPC
+    Frozen,
+    Confirmed,
+    Finalized,
+}
+
+/// Arrival of every stage, in the order of [`SlotStage::ALL`]
+type StageArrivals = [Option<Instant>; 5];
+
+impl SlotStage {
+    const ALL: [Self; 5] = [
+        Self::FirstShred,
+        Self::Processed,
+        Self::Frozen,
+        Self::Confirmed,
+        Self::Finalized,
+    ];
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::FirstShred => "first-shred",
+            Self::Processed => "processed",
+            Self::Frozen => "frozen",
+            Self::Confirmed => "confirmed",
+            Self::Finalized => "finalized",
+        }
+    }
+
+    const fn from_slot_update(update: &SlotUpdate) -> Option<(Slot, Self)> {
+        match update {
+            SlotUpdate::FirstShredReceived { slot, .. } => Some((*slot, Self::FirstShred)),
+            SlotUpdate::Frozen { slot, .. } => Some((*slot, Self::Frozen)),
+            SlotUpdate::OptimisticConfirmation { slot, .. } => Some((*slot, Self::Confirmed)),
+            SlotUpdate::Root { slot, .. } => Some((*slot, Self::Finalized)),
+            _ => None,
+        }
+    }
+
+    fn from_grpc(status: i32) -> Option<Self> {
+        match CommitmentLevel::try_from(status) {
+            Ok(CommitmentLevel::Processed) => Some(Self::Processed),
+            Ok(CommitmentLevel::Confirmed) => Some(Self::Confirmed),
+            Ok(CommitmentLevel::Finalized) => Some(Self::Finalized),
+            Err(_) => None,
+        }
+    }
+
+    /// Observed stages with time since the previous one, e.g.
+    /// `processed, confirmed +400ms, finalized +12.8s`
+    fn describe(arrivals: &StageArrivals) -> String {
+        let mut prev = None;
+        Self::ALL
+            .into_iter()
+            .zip(arrivals.iter())
+            .filter_map(|(stage, ts)| {
+                let ts = (*ts)?;
+                Some(match prev.replace(ts) {
+                    Some(prev) => {
+                        format!("{} +{:?}", stage.name(), ts.saturating_duration_since(prev))
+                    }
+                    None => stage.name().to_owned(),
+                })
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SourceKind {
+    PubSub,
+    Whirligig,
+    Grpc,
+}
+
+impl SourceKind {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::PubSub => "pubsub",
+            Self::Whirligig => "whirligig",
+            Self::Grpc => "grpc",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SourceEndpoint {
+    kind: SourceKind,
+    label: String,
+    endpoint: String,
+}
+
+impl SourceEndpoint {
+    fn slot_source(
+        &self,
+        x_token: Option<String>,
+        commitment: SlotStage,
+    ) -> anyhow::Result<Box<dyn Source<SlotItem>>> {
+        let endpoint = self.endpoint.clone();
+        Ok(match self.kind {
+            SourceKind::PubSub => Box::new(PubsubSlotSource {
+                endpoint,
+                commitment,
+            }),
+            SourceKind::Whirligig => Box::new(WhirligigSlotSource {
+                endpoint,
+                commitment,
+            }),
+            SourceKind::Grpc => {
+                anyhow::ensure!(
+                    matches!(
+                        commitment,
+                        SlotStage::Processed | SlotStage::Confirmed | SlotStage::Finalized
+                    ),
+                    "{}: gRPC does not support stage {}",
+                    self.label,
+                    commitment.name()
+                );
+                Box::new(GrpcSlotSource { endpoint, x_token })
+            }
+        })
+    }
+
+    fn account_source(
+        &self,
+        x_token: Option<String>,
+        filter: AccountFilter,
+    ) -> Box<dyn Source<AccountUpdate>> {
+        let endpoint = self.endpoint.clone();
+        match self.kind {
+            SourceKind::PubSub => Box::new(PubsubAccountSource { endpoint, filter }),
+            SourceKind::Whirligig => Box::new(WhirligigAccountSource { endpoint, filter }),
+            SourceKind::Grpc => Box::new(GrpcAccountSource {
+                endpoint,
+                x_token,
+                filter,
+            }),
+        }
+    }
+
+    fn transaction_source(
+        &self,
+        x_token: Option<String>,
+        accounts: Vec<Pubkey>,
+    ) -> Box<dyn Source<TransactionKey>> {
+        let endpoint = self.endpoint.clone();
+        match self.kind {
+            SourceKind::PubSub => Box::new(PubsubTransactionSource { endpoint, accounts }),
+            SourceKind::Whirligig => Box::new(WhirligigTransactionSource { endpoint, accounts }),
+            SourceKind::Grpc => Box::new(GrpcTransactionSource {
+                endpoint,
+                x_token,
+                accounts,
+            }),
+        }
+    }
+}
+
+/// Slots between processed and finalized stages are about 32, stages of older
+/// slots are dropped
+const STAGES_RETENTION: u64 = 512;
+
+/// Arrival time per source, in the order of [`Args::sources`]
+#[derive(Debug, Clone)]
+struct Arrivals(Vec<Option<Instant>>);
+
+impl AsRef<Arrivals> for Arrivals {
+    fn as_ref(&self) -> &Arrivals {
+        self
+    }
+}
+
+impl Arrivals {
+    fn new(sources: usize) -> Self {
+        Self(vec![None; sources])
+    }
+
+    /// Only the first arrival is kept, e.g. transaction can mention more than
+    /// one account
+    fn receive(&mut self, source: usize, ts: Instant) {
+        self.0[source].get_or_insert(ts);
+    }
+
+    fn is_complete(&self) -> bool {
+        self.0.iter().all(Option::is_some)
+    }
+
+    /// Delays relative to the first source, `None` if not received from source
+    fn delays(&self) -> Vec<Option<Duration>> {
+        let first = self.0.iter().flatten().min().copied();
+        self.0
+            .iter()
+            .map(|ts| Some((*ts)?.saturating_duration_since(first?)))
+            .collect()
+    }
+
+    /// Source which delivered first and lead over the second one
+    fn winner(&self) -> Option<(usize, Duration)> {
+        let mut delays = self
+            .delays()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(source, delay)| Some((source, delay?)))
+            .collect::<Vec<_>>();
+        delays.sort_by_key(|(_source, delay)| *delay);
+        match delays.as_slice() {
+            [(source, _), (_, lead), ..] => Some((*source, *lead)),
+            _ => None,
+        }
+    }
+}
+
+/// Histogram of durations in microseconds, up to one minute
+fn new_histogram() -> anyhow::Result<Histogram<u64>> {
+    Ok(Histogram::new_with_bounds(1, 60 * 1_000_000, 3)?)
+}
+
+fn as_micros(duration: Duration) -> u64 {
+    u64::try_from(duration.as_micros()).unwrap_or(u64::MAX)
+}
+
+#[derive(Debug)]
+struct SourceStats {
+    /// Delays relative to the first source in microseconds
+    delays: Histogram<u64>,
+    wins: u64,
+    missing: u64,
+    /// Time between consecutive stages of the slot in microseconds
+    stages: BTreeMap<(SlotStage, SlotStage), Histogram<u64>>,
+    last_arrival: Option<Instant>,
+    /// Last arrival before the stall, reset on the next arrival
+    stalled_since: Option<Instant>,
+    stalls: u64,
+}
+
+impl SourceStats {
+    fn new() -> anyhow::Result<Self> {
+        Ok(Self {
+            delays: new_histogram()?,
+            wins: 0,
+            missing: 0,
+            stages: BTreeMap::new(),
+            last_arrival: None,
+            stalled_since: None,
+            stalls: 0,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Stats {
+    labels: Vec<String>,
+    stall_timeout: Duration,
+    started: Instant,
+    started_at: SystemTime,
+    compared: u64,
+    sources: Vec<SourceStats>,
+}
+
+impl Stats {
+    fn new(labels: Vec<String>, stall_timeout: Duration) -> anyhow::Result<Self> {
+        Ok(Self {
+            stall_timeout,
+            sources: labels
+                .iter()
+                .map(|_label| SourceStats::new())
+                .collect::<anyhow::Result<_>>()?,
+            labels,
+            started: Instant::now(),
+            started_at: SystemTime::now(),
+            compared: 0,
+        })
+    }
+
+    /// Any arrival from source, used for stall detection
+    fn received(&mut self, source: usize, ts: Instant) {
+        let stats = &mut self.sources[source];
+        stats.last_arrival = Some(stats.last_arrival.map_or(ts, |last| last.max(ts)));
+        if let Some(since) = stats.stalled_since.take() {
+            eprintln!(
+                "{}: recovered after {:?}",
+                self.labels[source],
+                ts.saturating_duration_since(since)
+            );
+        }
+    }
+
+    /// Warn about sources without arrivals for `stall_timeout` while other
+    /// sources deliver
+    fn check_stalls(&mut self) {
+        let now = Instant::now();
+        let Some(latest) = self
+            .sources
+            .iter()
+            .filter_map(|stats| stats.last_arrival)
+            .max()
+        else {
+            return;
+        };
+        if now.saturating_duration_since(latest) > self.stall_timeout {
+            return;
+        }
+        for (label, stats) in self.labels.iter().zip(self.sources.iter_mut()) {
+            let last = stats.last_arrival.unwrap_or(self.started);
+            if stats.stalled_since.is_none()
+                && now.saturating_duration_since(last) > self.stall_timeout
+            {
+                eprintln!(
+                    "warning: {label} did not deliver anything for {:?}",
+                    now.saturating_duration_since(last)
+                );
+                stats.stalled_since = Some(last);
+                stats.stalls += 1;
+            }
+        }
+    }
+
+    /// Completed, reported or dropped entry, the same rules in all modes:
+    /// delays and the winner are recorded only if at least two sources
+    /// delivered the entry, source is counted as missing only if all other
+    /// sources delivered it
+    fn record(&mut self, arrivals: &Arrivals) {
+        let delivered = arrivals.0.iter().flatten().count();
+        if delivered + 1 == arrivals.0.len() {
+            for (stats, arrival) in self.sources.iter_mut().zip(arrivals.0.iter()) {
+                if arrival.is_none() {
+                    stats.missing += 1;
+                }
+            }
+        }
+        if delivered < 2 {
+            return;
+        }
+
+        self.compared += 1;
+        for (stats, delay) in self.sources.iter_mut().zip(arrivals.delays()) {
+            if let Some(delay) = delay {
+                stats.delays.saturating_record(as_micros(delay));
+            }
+        }
+        if let Some((winner, _lead)) = arrivals.winner() {
+            self.sources[winner].wins += 1;
+        }
+    }
+
+    fn record_stages(&mut self, source: usize, arrivals: &StageArrivals) -> anyhow::Result<()> {
+        let observed = SlotStage::ALL
+            .into_iter()
+            .zip(arrivals.iter())
+            .filter_map(|(stage, ts)| Some((stage, (*ts)?)))
+            .collect::<Vec<_>>();
+        for pair in observed.windows(2) {
+            if let [(from, from_ts), (to, to_ts)] = pair {
+                let histogram = match self.sources[source].stages.entry((*from, *to)) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => entry.insert(new_histogram()?),
+                };
+                histogram.saturating_record(as_micros(to_ts.saturating_duration_since(*from_ts)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove entries before `bound`, they are not expected to be completed
+    fn drop_older<K: Ord, V: AsRef<Arrivals>>(&mut self, entries: &mut BTreeMap<K, V>, bound: &K) {
+        let newer = entries.split_off(bound);
+        for entry in std::mem::replace(entries, newer).values() {
+            self.record(entry.as_ref());
+        }
+    }
+
+    /// Sources without arrival, e.g. `grpc, whirligig-2`
+    fn missed_by(&self, arrivals: &Arrivals) -> String {
+        self.labels
+            .iter()
+            .zip(arrivals.0.iter())
+            .filter(|(_label, ts)| ts.is_none())
+            .map(|(label, _ts)| label.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Delays of all sources for output, e.g. `pubsub +1ms, grpc missing`
+    fn describe(&self, arrivals: &Arrivals) -> String {
+        self.labels
+            .iter()
+            .zip(arrivals.delays())
+            .map(|(label, delay)| match delay {
+                Some(delay) => format!("{label} +{delay:?}"),
+                None => format!("{label} missing"),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn print_summary(&self) {
+        println!(
+            "summary: {} compared in {}",
+            self.compared,
+            humantime::format_duration(Duration::from_secs(self.started.elapsed().as_secs()))
+        );
+        let width = self
+            .labels
+            .iter()
+            .map(String::len)
+            .max()
+            .unwrap_or(0)
+            .max(10);
+        println!(
+            "  {:<width$} {:>10} {:>10} {:>10} {:>10} {:>8} {:>8} {:>8}",
+            "source", "p50", "p90", "p99", "max", "wins", "missing", "stalls"
+        );
+        for (source, stats) in self.labels.iter().zip(self.sources.iter()) {
+            let quantile = |quantile| {
+                format!(
+                    "{:?}",
+                    Duration::from_micros(stats.delays.value_at_quantile(quantile))
+                )
+            };
+            println!(
+                "  {:<width$} {:>10} {:>10} {:>10} {:>10} {:>8} {:>8} {:>8}",
+                source,
+                quantile(0.5),
+                quantile(0.9),
+                quantile(0.99),
+                format!("{:?}", Duration::from_micros(stats.delays.max())),
+                stats.wins,
+                stats.missing,
+                stats.stalls,
+            );
+        }
+
+        if self.sources.iter().any(|stats| !stats.stages.is_empty()) {
+            println!(
+                "  {:<width$} {:<12} {:<12} {:>10} {:>10} {:>10}",
+                "stages", "from", "to", "p50", "p90", "max"
+            );
+            for (source, stats) in self.labels.iter().zip(self.sources.iter()) {
+                for ((from, to), histogram) in stats.stages.iter() {
+                    let quantile = |quantile| {
+                        format!(
+                            "{:?}",
+                            Duration::from_micros(histogram.value_at_quantile(quantile))
+                        )
+                    };
+                    println!(
+                        "  {:<width$} {:<12} {:<12} {:>10} {:>10} {:>10}",
+                        source,
+                        from.name(),
+                        to.name(),
+                        quantile(0.5),
+                        quantile(0.9),
+                        format!("{:?}", Duration::from_micros(histogram.max())),
+                    );
+                }
+            }
+        }
+    }
+
+    fn write_hdr(&self, path: &str) -> anyhow::Result<()> {
+        let mut file = BufWriter::new(
+            File::create(path).with_context(|| format!("failed to create: {path}"))?,
+        );
+        let mut serializer = V2DeflateSerializer::new();
+        let mut writer = IntervalLogWriterBuilder::new()
+            .add_comment("ws-slot-perf: delays between sources and slot stages, microseconds")
+            .with_start_time(self.started_at)
+            .with_base_time(self.started_at)
+            .begin_log_with(&mut file, &mut serializer)?;
+        for (source, stats) in self.labels.iter().zip(self.sources.iter()) {
+            writer.write_histogram(
+                &stats.delays,
+                Duration::ZERO,
+                self.started.elapsed(),
+                Tag::new(source),
+            )?;
+            for ((from, to), histogram) in stats.stages.iter() {
+                let tag = format!("{source}:{}-{}", from.name(), to.name());
+                writer.write_histogram(
+                    histogram,
+                    Duration::ZERO,
+                    self.started.elapsed(),
+                    Tag::new(&tag),
+                )?;
+            }
+        }
+        drop(writer);
+        file.flush()?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+enum ControlEvent {
+    Summary,
+    StallCheck,
+    Stop,
+}
+
+/// Stop conditions and summary interval shared by all modes
+#[derive(Debug)]
+struct Control {
+    summary: Interval,
+    stall_check: Interval,
+    ctrl_c: oneshot::Receiver<()>,
+    deadline: Option<Pin<Box<Sleep>>>,
+    slots: Option<u64>,
+    first_slot: Option<Slot>,
+    retention: u64,
+    latest_slot: Slot,
+}
+
+impl Control {
+    fn new(args: &Args) -> Self {
+        let (ctrl_c_tx, ctrl_c) = oneshot::channel();
+        tokio::spawn(async move {
+            if signal::ctrl_c().await.is_ok() {
+                let _ = ctrl_c_tx.send(());
+            }
+        });
+
+        let mut summary = time::interval_at(
+            time::Instant::now() + args.summary_interval,
+            args.summary_interval,
+        );
+        summary.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut stall_check = time::interval(Duration::from_secs(1));
+        stall_check.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        Self {
+            summary,
+            stall_check,
+            ctrl_c,
+            deadline: args
+                .duration
+                .map(|duration| Box::pin(time::sleep(duration))),
+            slots: args.slots,
+            first_slot: None,
+            retention: args.retention,
+            latest_slot: 0,
+        }
+    }
+
+    async fn wait(&mut self) -> ControlEvent {
+        let deadline = async {
+            match &mut self.deadline {
+                Some(deadline) => deadline.await,
+                None => future::pending().await,
+            }
+        };
+        tokio::select! {
+            _ = self.summary.tick() => ControlEvent::Summary,
+            _ = self.stall_check.tick() => ControlEvent::StallCheck,
+            _ = &mut self.ctrl_c => ControlEvent::Stop,
+            () = deadline => ControlEvent::Stop,
+        }
+    }
+
+    /// Check `--slots` limit on every compared item
+    fn is_done(&mut self, slot: Slot) -> bool {
+        let first_slot = *self.first_slot.get_or_insert(slot);
+        self.slots
+            .map(|slots| slot >= first_slot + slots)
+            .unwrap_or(false)
+    }
+
+    /// Track the latest slot, items before returned slot should be evicted
+    fn retention_bound(&mut self, slot: Slot) -> Option<Slot> {
+        self.latest_slot = self.latest_slot.max(slot);
+        self.latest_slot.checked_sub(self.retention)
+    }
+}
+
+/// Account update from any source, WebSocket notifications do not include
+/// `write_version`, so updates are matched by hash of account state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct AccountUpdateKey {
+    slot: Slot,
+    pubkey: Pubkey,
+    hash: Hash,
+}
+
+impl AccountUpdateKey {
+    fn new(slot: Slot, pubkey: Pubkey, lamports: u64, owner: &[u8], data: &[u8]) -> Self {
+        Self {
+            slot,
+            pubkey,
+            hash: hashv(&[&lamports.to_le_bytes(), owner, data]),
+        }
+    }
+
+    fn from_ui(slot: Slot, pubkey: Pubkey, account: UiAccount) -> anyhow::Result<Self> {
+        let account: Account = account
+            .decode()
+            .with_context(|| format!("failed to decode account: {pubkey}"))?;
+        Ok(Self::new(
+            slot,
+            pubkey,
+            account.lamports,
+            account.owner.as_ref(),
+            &account.data,
+        ))
+    }
+
+    fn from_keyed(slot: Slot, pubkey: &str, account: UiAccount) -> anyhow::Result<Self> {
+        let pubkey =
+            Pubkey::from_str(pubkey).with_context(|| format!("invalid pubkey: {pubkey}"))?;
+        Self::from_ui(slot, pubkey, account)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct AccountArrivals {
+    arrivals: Arrivals,
+    write_version: Option<u64>,
 }
 
-#[derive(Debug, Default, Clone, Copy)]
-struct Slots {
-    pubsub: Option<Instant>,
-    whirligig: Option<Instant>,
-    grpc: Option<Instant>,
+impl AccountArrivals {
+    fn new(sources: usize) -> Self {
+        Self {
+            arrivals: Arrivals::new(sources),
+            write_version: None,
+        }
+    }
+}
+
+impl AsRef<Arrivals> for AccountArrivals {
+    fn as_ref(&self) -> &Arrivals {
+        &self.arrivals
+    }
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
-    let client_pubsub = PubsubClient::new(&args.pubsub).await?;
-    let mut subscribe_pubsub = client_pubsub.slot_subscribe().await?.0;
+    let sources = args.sources()?;
+    let action = args.action.clone().unwrap_or(ArgsAction::Slot {
+        commitment: SlotStage::default(),
+    });
+
+    let mut stats = Stats::new(
+        sources.iter().map(|source| source.label.clone()).collect(),
+        args.stall_timeout,
+    )?;
+    let mut control = Control::new(&args);
+    let hdr_dump = args.hdr_dump.clone();
+    let result = match action {
+        ArgsAction::Slot { commitment } => {
+            run_slot(args, sources, commitment, &mut stats, &mut control).await
+        }
+        ArgsAction::Account { pubkey, program } => {
+            run_account(args, sources, pubkey, program, &mut stats, &mut control).await
+        }
+        ArgsAction::Transaction { account } => {
+            run_transaction(args, sources, account, &mut stats, &mut control).await
+        }
+    };
+
+    // collected stats are reported even if a source is closed or failed
+    stats.print_summary();
+    if let Some(path) = hdr_dump {
+        stats.write_hdr(&path)?;
+    }
+    result
+}
 
-    let client_whirligig = PubsubClient::new(&args.whirligig).await?;
-    let mut subscribe_whirligig = client_whirligig.slot_subscribe().await?.0;
+/// Item received from source with index in [`Args::sources`]
+type Arrival<T> = (usize, T, Instant);
 
-    let mut client = GeyserGrpcClient::connect(args.grpc, args.x_token, None)?;
-    let (mut subscribe_tx, mut grpc) = client.subscribe().await?;
-    subscribe_tx
-        .send(SubscribeRequest {
-            slots: hashmap! { "".to_owned() => SubscribeRequestFilterSlots { filter_by_commitment: Some(true) } },
-            commitment: Some(CommitmentLevel::Processed as i32),
-            ..Default::default()
-        })
-        .await?;
+#[derive(Debug)]
+struct ArrivalSender<T> {
+    source: usize,
+    tx: mpsc::UnboundedSender<Arrival<T>>,
+}
 
-    let mut slots = BTreeMap::<u64, Slots>::new();
-    loop {
-        let slot = tokio::select! {
-            Some(SlotInfo{slot,..}) = subscribe_pubsub.next() => {
-                slots.entry(slot).or_default().pubsub = Some(Instant::now());
-                slot
-            }
-            Some(SlotInfo{slot,..}) = subscribe_whirligig.next() => {
-                slots.entry(slot).or_default().whirligig = Some(Instant::now());
-                slot
-            }
-            Some(msg) = grpc.next() => {
-                if let SubscribeUpdate { update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot { slot, .. })), .. } = msg? {
-                    slots.entry(slot).or_default().grpc = Some(Instant::now());
-                    slot
-                } else {
-                    continue
+impl<T> ArrivalSender<T> {
+    fn send(&self, item: T) -> anyhow::Result<()> {
+        self.tx
+            .send((self.source, item, Instant::now()))
+            .map_err(|_| anyhow::anyhow!("arrivals receiver is closed"))
+    }
+}
+
+/// Source of compared items, implemented for the item of every mode: slot
+/// stages, account updates and transactions. New sources implement the trait
+/// and are created in [`SourceEndpoint`]
+trait Source<T>: Send {
+    /// Subscribe and send received items until the stream is closed
+    fn run(self: Box<Self>, tx: ArrivalSender<T>) -> BoxFuture<'static, anyhow::Result<()>>;
+}
+
+/// Run every source in own task
+fn spawn_sources<T: Send + 'static>(
+    sources: &[SourceEndpoint],
+    mut create: impl FnMut(&SourceEndpoint) -> anyhow::Result<Box<dyn Source<T>>>,
+) -> anyhow::Result<(
+    JoinSet<anyhow::Result<()>>,
+    mpsc::UnboundedReceiver<Arrival<T>>,
+)> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let mut tasks = JoinSet::new();
+    for (index, source) in sources.iter().enumerate() {
+        let label = source.label.clone();
+        let item_source = create(source)?;
+        let tx = ArrivalSender {
+            source: index,
+            tx: tx.clone(),
+        };
+        tasks.spawn(async move {
+            match item_source.run(tx).await {
+                Ok(()) => anyhow::bail!("{label}: stream is closed"),
+                Err(error) => Err(error.context(format!("{label}: source failed"))),
+            }
+        });
+    }
+    Ok((tasks, rx))
+}
+
+/// Slot stage, sources send the compared stage and any other stages available
+/// with the same subscription
+type SlotItem = (Slot, SlotStage);
+
+/// `slotsUpdatesSubscribe` for stages of every slot, `slotSubscribe` only if
+/// processed stage is compared
+#[derive(Debug)]
+struct PubsubSlotSource {
+    endpoint: String,
+    commitment: SlotStage,
+}
+
+impl Source<SlotItem> for PubsubSlotSource {
+    fn run(
+        self: Box<Self>,
+        slots_tx: ArrivalSender<SlotItem>,
+    ) -> BoxFuture<'static, anyhow::Result<()>> {
+        async move {
+            let client = PubsubClient::new(&self.endpoint).await?;
+            let mut streams: Vec<BoxStream<'_, Option<SlotItem>>> = vec![];
+            let (stream, _) = client.slot_updates_subscribe().await?;
+            streams.push(
+                stream
+                    .map(|update| SlotStage::from_slot_update(&update))
+                    .boxed(),
+            );
+            if self.commitment == SlotStage::Processed {
+                let (stream, _) = client.slot_subscribe().await?;
+                streams.push(
+                    stream
+                        .map(|SlotInfo { slot, .. }| Some((slot, SlotStage::Processed)))
+                        .boxed(),
+                );
+            }
+
+            let mut stream = stream::select_all(streams);
+            while let Some(item) = stream.next().await {
+                if let Some(item) = item {
+                    slots_tx.send(item)?;
+                }
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// Same subscriptions as [`PubsubSlotSource`]
+#[derive(Debug)]
+struct WhirligigSlotSource {
+    endpoint: String,
+    commitment: SlotStage,
+}
+
+impl Source<SlotItem> for WhirligigSlotSource {
+    fn run(
+        self: Box<Self>,
+        slots_tx: ArrivalSender<SlotItem>,
+    ) -> BoxFuture<'static, anyhow::Result<()>> {
+        async move {
+            let client = WhirligigClient::connect(&self.endpoint).await?;
+            let mut streams: Vec<BoxStream<'_, anyhow::Result<Option<SlotItem>>>> = vec![];
+            let stream = client.slots_updates_subscribe().await?;
+            streams.push(
+                stream
+                    .map(|msg| -> anyhow::Result<_> { Ok(SlotStage::from_slot_update(&msg?)) })
+                    .boxed(),
+            );
+            if self.commitment == SlotStage::Processed {
+                let stream = client.slot_subscribe().await?;
+                streams.push(
+                    stream
+                        .map(|msg| -> anyhow::Result<_> {
+                            let SlotInfo { slot, .. } = msg?;
+                            Ok(Some((slot, SlotStage::Processed)))
+                        })
+                        .boxed(),
+                );
+            }
+
+            let mut stream = stream::select_all(streams);
+            while let Some(item) = stream.next().await {
+                if let Some(item) = item? {
+                    slots_tx.send(item)?;
+                }
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// Slots with every commitment level
+#[derive(Debug)]
+struct GrpcSlotSource {
+    endpoint: String,
+    x_token: Option<String>,
+}
+
+impl Source<SlotItem> for GrpcSlotSource {
+    fn run(
+        self: Box<Self>,
+        slots_tx: ArrivalSender<SlotItem>,
+    ) -> BoxFuture<'static, anyhow::Result<()>> {
+        async move {
+            let Self { endpoint, x_token } = *self;
+            let mut client = GeyserGrpcClient::connect(endpoint, x_token, None)?;
+            let (mut subscribe_tx, mut stream) = client.subscribe().await?;
+            subscribe_tx
+                .send(SubscribeRequest {
+                    slots: hashmap! { "".to_owned() => SubscribeRequestFilterSlots { filter_by_commitment: Some(false) } },
+                    commitment: Some(CommitmentLevel::Processed as i32),
+                    ..Default::default()
+                })
+                .await?;
+            while let Some(msg) = stream.next().await {
+                if let SubscribeUpdate {
+                    update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot { slot, status, .. })),
+                    ..
+                } = msg?
+                {
+                    if let Some(stage) = SlotStage::from_grpc(status) {
+                        slots_tx.send((slot, stage))?;
+                    }
                 }
             }
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+async fn run_slot(
+    args: Args,
+    sources: Vec<SourceEndpoint>,
+    commitment: SlotStage,
+    stats: &mut Stats,
+    control: &mut Control,
+) -> anyhow::Result<()> {
+    let (mut tasks, mut slots_rx) = spawn_sources(&sources, |source| {
+        source.slot_source(args.x_token.clone(), commitment)
+    })?;
+
+    // arrivals of the compared stage
+    let mut slots = BTreeMap::<Slot, Arrivals>::new();
+    // arrivals of all stages per source, kept until finalized or too old
+    let mut stages = BTreeMap::<Slot, Vec<StageArrivals>>::new();
+    loop {
+        let (source, (slot, stage), ts) = tokio::select! {
+            Some(arrival) = slots_rx.recv() => arrival,
+            Some(result) = tasks.join_next() => return result?,
+            event = control.wait() => match event {
+                ControlEvent::Summary => {
+                    stats.print_summary();
+                    continue;
+                }
+                ControlEvent::StallCheck => {
+                    stats.check_stalls();
+                    continue;
+                }
+                ControlEvent::Stop => return Ok(()),
+            },
         };
 
-        if let Some(Slots {
-            pubsub: Some(pubsub),
-            whirligig: Some(whirligig),
-            grpc: Some(grpc),
-        }) = slots.get(&slot).copied()
-        {
+        stats.received(source, ts);
+        let arrivals = &mut stages
+            .entry(slot)
+            .or_insert_with(|| vec![[None; 5]; sources.len()])[source];
+        arrivals[stage as usize].get_or_insert(ts);
+        if stage == SlotStage::Finalized {
+            println!(
+                "{slot} {} stages: {}",
+                sources[source].label,
+                SlotStage::describe(arrivals)
+            );
+            stats.record_stages(source, arrivals)?;
+        }
+        if let Some(bound) = slot.checked_sub(STAGES_RETENTION) {
+            stages = stages.split_off(&bound);
+        }
+
+        if stage != commitment {
+            continue;
+        }
+        slots
+            .entry(slot)
+            .or_insert_with(|| Arrivals::new(sources.len()))
+            .receive(source, ts);
+        if let Some(bound) = control.retention_bound(slot) {
+            let newer = slots.split_off(&bound);
+            for (slot, arrivals) in std::mem::replace(&mut slots, newer) {
+                println!(
+                    "{slot}: {}, evicted, missed by {}",
+                    stats.describe(&arrivals),
+                    stats.missed_by(&arrivals)
+                );
+                stats.record(&arrivals);
+            }
+        }
+        if slots.get(&slot).is_some_and(Arrivals::is_complete) {
             // The issue why PubSub looks better: https://github.com/solana-labs/solana/issues/32958
 
-            let first = pubsub.min(whirligig.min(grpc));
+            // older slots are reported with missing sources
+            let mut reported = slots.split_off(&(slot + 1));
+            std::mem::swap(&mut slots, &mut reported);
+            for (slot, arrivals) in reported {
+                println!("{slot}: {}", stats.describe(&arrivals));
+                stats.record(&arrivals);
+            }
+            if control.is_done(slot) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Accounts to compare in `account` mode
+#[derive(Debug, Clone)]
+struct AccountFilter {
+    pubkeys: Vec<Pubkey>,
+    program: Option<Pubkey>,
+}
+
+impl AccountFilter {
+    const fn account_config() -> RpcAccountInfoConfig {
+        RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            data_slice: None,
+            commitment: Some(CommitmentConfig::processed()),
+            min_context_slot: None,
+        }
+    }
+
+    const fn program_config() -> RpcProgramAccountsConfig {
+        RpcProgramAccountsConfig {
+            filters: None,
+            account_config: Self::account_config(),
+            with_context: Some(true),
+        }
+    }
+}
+
+/// Account update, `write_version` is available only from gRPC
+#[derive(Debug)]
+struct AccountUpdate {
+    key: AccountUpdateKey,
+    write_version: Option<u64>,
+}
+
+/// `accountSubscribe` per pubkey and `programSubscribe`
+#[derive(Debug)]
+struct PubsubAccountSource {
+    endpoint: String,
+    filter: AccountFilter,
+}
+
+impl Source<AccountUpdate> for PubsubAccountSource {
+    fn run(
+        self: Box<Self>,
+        updates_tx: ArrivalSender<AccountUpdate>,
+    ) -> BoxFuture<'static, anyhow::Result<()>> {
+        async move {
+            let client = PubsubClient::new(&self.endpoint).await?;
+            let mut streams: Vec<BoxStream<'_, anyhow::Result<AccountUpdateKey>>> = vec![];
+            if let Some(program) = &self.filter.program {
+                let (stream, _) = client
+                    .program_subscribe(program, Some(AccountFilter::program_config()))
+                    .await?;
+                streams.push(
+                    stream
+                        .map(|msg| {
+                            AccountUpdateKey::from_keyed(
+                                msg.context.slot,
+                                &msg.value.pubkey,
+                                msg.value.account,
+                            )
+                        })
+                        .boxed(),
+                );
+            }
+            for pubkey in self.filter.pubkeys.iter().copied() {
+                let (stream, _) = client
+                    .account_subscribe(&pubkey, Some(AccountFilter::account_config()))
+                    .await?;
+                streams.push(
+                    stream
+                        .map(move |msg| {
+                            AccountUpdateKey::from_ui(msg.context.slot, pubkey, msg.value)
+                        })
+                        .boxed(),
+                );
+            }
+
+            let mut stream = stream::select_all(streams);
+            while let Some(key) = stream.next().await {
+                updates_tx.send(AccountUpdate {
+                    key: key?,
+                    write_version: None,
+                })?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// Same subscriptions as [`PubsubAccountSource`]
+#[derive(Debug)]
+struct WhirligigAccountSource {
+    endpoint: String,
+    filter: AccountFilter,
+}
+
+impl Source<AccountUpdate> for WhirligigAccountSource {
+    fn run(
+        self: Box<Self>,
+        updates_tx: ArrivalSender<AccountUpdate>,
+    ) -> BoxFuture<'static, anyhow::Result<()>> {
+        async move {
+            let client = WhirligigClient::connect(&self.endpoint).await?;
+            let mut streams: Vec<BoxStream<'_, anyhow::Result<AccountUpdateKey>>> = vec![];
+            if let Some(program) = &self.filter.program {
+                let stream = client
+                    .program_subscribe(program, Some(AccountFilter::program_config()))
+                    .await?;
+                streams.push(
+                    stream
+                        .map(|msg| {
+                            let msg = msg?;
+                            AccountUpdateKey::from_keyed(
+                                msg.context.slot,
+                                &msg.value.pubkey,
+                                msg.value.account,
+                            )
+                        })
+                        .boxed(),
+                );
+            }
+            for pubkey in self.filter.pubkeys.iter().copied() {
+                let stream = client
+                    .account_subscribe(&pubkey, Some(AccountFilter::account_config()))
+                    .await?;
+                streams.push(
+                    stream
+                        .map(move |msg| {
+                            let msg = msg?;
+                            AccountUpdateKey::from_ui(msg.context.slot, pubkey, msg.value)
+                        })
+                        .boxed(),
+                );
+            }
+
+            let mut stream = stream::select_all(streams);
+            while let Some(key) = stream.next().await {
+                updates_tx.send(AccountUpdate {
+                    key: key?,
+                    write_version: None,
+                })?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// One subscription with accounts and owners, every write is received
+#[derive(Debug)]
+struct GrpcAccountSource {
+    endpoint: String,
+    x_token: Option<String>,
+    filter: AccountFilter,
+}
+
+impl Source<AccountUpdate> for GrpcAccountSource {
+    fn run(
+        self: Box<Self>,
+        updates_tx: ArrivalSender<AccountUpdate>,
+    ) -> BoxFuture<'static, anyhow::Result<()>> {
+        async move {
+            let Self {
+                endpoint,
+                x_token,
+                filter,
+            } = *self;
+            let mut client = GeyserGrpcClient::connect(endpoint, x_token, None)?;
+            let (mut subscribe_tx, mut stream) = client.subscribe().await?;
+            subscribe_tx
+                .send(SubscribeRequest {
+                    accounts: hashmap! { "".to_owned() => SubscribeRequestFilterAccounts {
+                        account: filter.pubkeys.iter().map(|pubkey| pubkey.to_string()).collect(),
+                        owner: filter.program.iter().map(|program| program.to_string()).collect(),
+                        ..Default::default()
+                    } },
+                    commitment: Some(CommitmentLevel::Processed as i32),
+                    ..Default::default()
+                })
+                .await?;
+            while let Some(msg) = stream.next().await {
+                let SubscribeUpdate {
+                    update_oneof:
+                        Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                            account:
+                                Some(SubscribeUpdateAccountInfo {
+                                    pubkey,
+                                    lamports,
+                                    owner,
+                                    data,
+                                    write_version,
+                                    ..
+                                }),
+                            slot,
+                            ..
+                        })),
+                    ..
+                } = msg?
+                else {
+                    continue;
+                };
+                let pubkey = Pubkey::try_from(pubkey.as_slice())
+                    .map_err(|_| anyhow::anyhow!("invalid pubkey in gRPC update"))?;
+                updates_tx.send(AccountUpdate {
+                    key: AccountUpdateKey::new(slot, pubkey, lamports, &owner, &data),
+                    write_version: Some(write_version),
+                })?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+async fn run_account(
+    args: Args,
+    sources: Vec<SourceEndpoint>,
+    pubkeys: Vec<String>,
+    program: Option<String>,
+    stats: &mut Stats,
+    control: &mut Control,
+) -> anyhow::Result<()> {
+    let filter = AccountFilter {
+        pubkeys: pubkeys
+            .iter()
+            .map(|pubkey| {
+                Pubkey::from_str(pubkey).with_context(|| format!("invalid pubkey: {pubkey}"))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?,
+        program: program
+            .map(|program| {
+                Pubkey::from_str(&program).with_context(|| format!("invalid program: {program}"))
+            })
+            .transpose()?,
+    };
+    let (mut tasks, mut updates_rx) = spawn_sources(&sources, |source| {
+        Ok(source.account_source(args.x_token.clone(), filter.clone()))
+    })?;
+
+    let mut updates = BTreeMap::<AccountUpdateKey, AccountArrivals>::new();
+    loop {
+        let (source, AccountUpdate { key, write_version }, ts) = tokio::select! {
+            Some(arrival) = updates_rx.recv() => arrival,
+            Some(result) = tasks.join_next() => return result?,
+            event = control.wait() => match event {
+                ControlEvent::Summary => {
+                    stats.print_summary();
+                    continue;
+                }
+                ControlEvent::StallCheck => {
+                    stats.check_stalls();
+                    continue;
+                }
+                ControlEvent::Stop => return Ok(()),
+            },
+        };
+
+        stats.received(source, ts);
+        let entry = updates
+            .entry(key)
+            .or_insert_with(|| AccountArrivals::new(sources.len()));
+        entry.arrivals.receive(source, ts);
+        if write_version.is_some() {
+            entry.write_version = write_version;
+        }
+
+        if let Some(slot) = control.retention_bound(key.slot) {
+            let bound = AccountUpdateKey {
+                slot,
+                pubkey: Pubkey::default(),
+                hash: Hash::default(),
+            };
+            stats.drop_older(&mut updates, &bound);
+        }
+        let Some(entry) = updates.get(&key) else {
+            continue;
+        };
+        if entry.arrivals.is_complete() {
             println!(
-                "{slot}: pubsub +{:?}, whirligig +{:?}, grpc +{:?}",
-                pubsub.saturating_duration_since(first),
-                whirligig.saturating_duration_since(first),
-                grpc.saturating_duration_since(first)
+                "{} slot {} write_version {}: {}",
+                key.pubkey,
+                key.slot,
+                entry.write_version.unwrap_or_default(),
+                stats.describe(&entry.arrivals),
             );
+            stats.record(&entry.arrivals);
 
-            loop {
-                match slots.keys().next().copied() {
-                    Some(first_slot) if first_slot <= slot => {
-                        slots.remove(&first_slot);
-                    }
-                    _ => break,
+            // gRPC sends every write, WebSocket only the latest state in the
+            // slot, so not matched updates of the same account from older
+            // slots are dropped, other accounts are evicted by retention
+            updates.remove(&key);
+            let bound = AccountUpdateKey {
+                slot: key.slot,
+                pubkey: Pubkey::default(),
+                hash: Hash::default(),
+            };
+            let older = updates
+                .range(..bound)
+                .map(|(older, _entry)| *older)
+                .filter(|older| older.pubkey == key.pubkey)
+                .collect::<Vec<_>>();
+            for older in older {
+                if let Some(entry) = updates.remove(&older) {
+                    stats.record(&entry.arrivals);
                 }
             }
+            if control.is_done(key.slot) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Transaction is matched by slot and signature
+type TransactionKey = (Slot, Signature);
+
+/// `logsSubscribe` per account, PubSub supports only one account in `mentions`,
+/// so transaction which mentions a few accounts is sent a few times
+#[derive(Debug)]
+struct PubsubTransactionSource {
+    endpoint: String,
+    accounts: Vec<Pubkey>,
+}
+
+impl Source<TransactionKey> for PubsubTransactionSource {
+    fn run(
+        self: Box<Self>,
+        transactions_tx: ArrivalSender<TransactionKey>,
+    ) -> BoxFuture<'static, anyhow::Result<()>> {
+        async move {
+            let client = PubsubClient::new(&self.endpoint).await?;
+            let mut streams = vec![];
+            for account in self.accounts.iter() {
+                let (stream, _) = client
+                    .logs_subscribe(
+                        RpcTransactionLogsFilter::Mentions(vec![account.to_string()]),
+                        RpcTransactionLogsConfig {
+                            commitment: Some(CommitmentConfig::processed()),
+                        },
+                    )
+                    .await?;
+                streams.push(stream);
+            }
+
+            let mut stream = stream::select_all(streams);
+            while let Some(msg) = stream.next().await {
+                let signature = Signature::from_str(&msg.value.signature)
+                    .with_context(|| format!("invalid signature: {}", msg.value.signature))?;
+                transactions_tx.send((msg.context.slot, signature))?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// `transactionSubscribe` with all accounts in `include`
+#[derive(Debug)]
+struct WhirligigTransactionSource {
+    endpoint: String,
+    accounts: Vec<Pubkey>,
+}
+
+impl Source<TransactionKey> for WhirligigTransactionSource {
+    fn run(
+        self: Box<Self>,
+        transactions_tx: ArrivalSender<TransactionKey>,
+    ) -> BoxFuture<'static, anyhow::Result<()>> {
+        async move {
+            let mut stream = WhirligigClient::connect(&self.endpoint)
+                .await?
+                .transaction_subscribe(
+                    TransactionSubscribeFilter {
+                        vote: Some(false),
+                        failed: None,
+                        signature: None,
+                        accounts: TransactionSubscribeFilterAccounts {
+                            include: self
+                                .accounts
+                                .iter()
+                                .map(|pubkey| pubkey.to_string())
+                                .collect(),
+                            ..Default::default()
+                        },
+                    },
+                    TransactionSubscribeConfig {
+                        commitment: Some(CommitmentConfig::processed()),
+                        transaction_details: Some(TransactionDetails::None),
+                        max_supported_transaction_version: Some(0),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+            while let Some(msg) = stream.next().await {
+                let TransactionNotification {
+                    signature, slot, ..
+                } = msg?;
+                let signature = Signature::from_str(&signature)
+                    .with_context(|| format!("invalid signature: {signature}"))?;
+                transactions_tx.send((slot, signature))?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// Non-vote transactions with any of accounts
+#[derive(Debug)]
+struct GrpcTransactionSource {
+    endpoint: String,
+    x_token: Option<String>,
+    accounts: Vec<Pubkey>,
+}
+
+impl Source<TransactionKey> for GrpcTransactionSource {
+    fn run(
+        self: Box<Self>,
+        transactions_tx: ArrivalSender<TransactionKey>,
+    ) -> BoxFuture<'static, anyhow::Result<()>> {
+        async move {
+            let Self {
+                endpoint,
+                x_token,
+                accounts,
+            } = *self;
+            let mut client = GeyserGrpcClient::connect(endpoint, x_token, None)?;
+            let (mut subscribe_tx, mut stream) = client.subscribe().await?;
+            subscribe_tx
+                .send(SubscribeRequest {
+                    transactions: hashmap! { "".to_owned() => SubscribeRequestFilterTransactions {
+                        vote: Some(false),
+                        account_include: accounts.iter().map(|pubkey| pubkey.to_string()).collect(),
+                        ..Default::default()
+                    } },
+                    commitment: Some(CommitmentLevel::Processed as i32),
+                    ..Default::default()
+                })
+                .await?;
+            while let Some(msg) = stream.next().await {
+                let SubscribeUpdate {
+                    update_oneof:
+                        Some(UpdateOneof::Transaction(SubscribeUpdateTransaction {
+                            transaction: Some(SubscribeUpdateTransactionInfo { signature, .. }),
+                            slot,
+                            ..
+                        })),
+                    ..
+                } = msg?
+                else {
+                    continue;
+                };
+                let signature = Signature::try_from(signature.as_slice())
+                    .map_err(|_| anyhow::anyhow!("invalid signature in gRPC update"))?;
+                transactions_tx.send((slot, signature))?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+async fn run_transaction(
+    args: Args,
+    sources: Vec<SourceEndpoint>,
+    accounts: Vec<String>,
+    stats: &mut Stats,
+    control: &mut Control,
+) -> anyhow::Result<()> {
+    let accounts = accounts
+        .iter()
+        .map(|pubkey| Pubkey::from_str(pubkey).with_context(|| format!("invalid pubkey: {pubkey}")))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let (mut tasks, mut transactions_rx) = spawn_sources(&sources, |source| {
+        Ok(source.transaction_source(args.x_token.clone(), accounts.clone()))
+    })?;
+
+    let mut transactions = BTreeMap::<TransactionKey, Arrivals>::new();
+    // PubSub sends transaction once per mentioned account, copies arriving
+    // after the transaction is completed are ignored
+    let mut completed = BTreeSet::<TransactionKey>::new();
+    loop {
+        let (source, key, ts) = tokio::select! {
+            Some(arrival) = transactions_rx.recv() => arrival,
+            Some(result) = tasks.join_next() => return result?,
+            event = control.wait() => match event {
+                ControlEvent::Summary => {
+                    stats.print_summary();
+                    continue;
+                }
+                ControlEvent::StallCheck => {
+                    stats.check_stalls();
+                    continue;
+                }
+                ControlEvent::Stop => return Ok(()),
+            },
+        };
+
+        stats.received(source, ts);
+        if let Some(slot) = control.retention_bound(key.0) {
+            let bound = (slot, Signature::default());
+            stats.drop_older(&mut transactions, &bound);
+            completed = completed.split_off(&bound);
+        }
+        if completed.contains(&key) {
+            continue;
+        }
+        transactions
+            .entry(key)
+            .or_insert_with(|| Arrivals::new(sources.len()))
+            .receive(source, ts);
+
+        let Some(arrivals) = transactions.get(&key) else {
+            continue;
+        };
+        if let (true, Some((winner, lead))) = (arrivals.is_complete(), arrivals.winner()) {
+            let (slot, signature) = key;
+            println!(
+                "{} slot {}: {} first by {:?}, {}",
+                signature,
+                slot,
+                stats.labels[winner],
+                lead,
+                stats.describe(arrivals),
+            );
+            stats.record(arrivals);
+
+            // other transactions are evicted by retention
+            transactions.remove(&key);
+            completed.insert(key);
+            if control.is_done(slot) {
+                return Ok(());
+            }
         }
     }
 }
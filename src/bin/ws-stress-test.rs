@@ -1,65 +1,60 @@
 use {
     anyhow::Context,
     clap::Parser,
-    futures::stream::StreamExt,
-    indicatif::{ProgressBar, ProgressStyle},
-    solana_client::{
-        nonblocking::pubsub_client::PubsubClient,
-        rpc_config::{RpcTransactionLogsConfig, RpcTransactionLogsFilter},
+    futures::{
+        channel::mpsc,
+        future::{BoxFuture, FutureExt},
+        stream::StreamExt,
+    },
+    hdrhistogram::Histogram as HdrHistogram,
+    hyper::{
+        service::{make_service_fn, service_fn},
+        Body, Request, Response, Server, StatusCode,
+    },
+    indicatif::{MultiProgress, ProgressBar, ProgressStyle},
+    prometheus::{
+        Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts,
+        Registry, TextEncoder,
+    },
+    serde::{Deserialize, Deserializer, Serialize},
+    solana_account_decoder::UiAccountEncoding,
+    solana_client::rpc_config::{
+        RpcAccountInfoConfig, RpcBlockSubscribeConfig, RpcBlockSubscribeFilter,
+        RpcProgramAccountsConfig, RpcTransactionLogsConfig, RpcTransactionLogsFilter,
+    },
+    solana_rpc_client_api::response::{Response as RpcResponse, RpcVote, SlotInfo, SlotUpdate},
+    solana_sdk::{clock::Slot, commitment_config::CommitmentConfig, pubkey::Pubkey},
+    solana_transaction_status::{TransactionDetails, UiTransactionEncoding},
+    std::{
+        collections::{BTreeMap, HashMap, HashSet},
+        convert::Infallible,
+        net::SocketAddr,
+        path::{Path, PathBuf},
+        str::FromStr,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Mutex, MutexGuard,
+        },
     },
-    solana_sdk::pubkey::Pubkey,
-    std::{borrow::Cow, str::FromStr, sync::Arc},
     tokio::{
-        fs,
+        fs, signal,
+        sync::broadcast,
         task::JoinSet,
-        time::{sleep, Duration},
+        time::{interval, sleep, sleep_until, Duration, Instant},
+    },
+    whirligig_client::{
+        client::{
+            ClientConfig, ClientError, ClientResult, ConnectionEvent, ReconnectConfig,
+            Subscription, SubscriptionStats, WhirligigClient,
+        },
+        types::{
+            TransactionNotification, TransactionSubscribeConfig, TransactionSubscribeFilter,
+            TransactionSubscribeFilterAccounts,
+        },
     },
 };
 
-const ACCOUNTS: &str = "";
-const OWNERS: &str = "
-11111111111111111111111111111111
-26LYr2NRPprQ7aq6HTyAvrWxhouH8c9KLv1KumtRTJu2
-2V7t5NaKY7aGkwytCWQgvUYZfEr9XMwNChhJEakTExk6
-5ALDzwcRJfSyGdGyhP3kP628aqBNHZzLuVww7o9kdspe
-5JQ8Mhdp2wv3HWcfjq9Ts8kwzCAeBADFBDAgBznzRsE4
-675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8
-7Sur3cy2efJGv8Qomn35p5k6HqMMcE8juWMBX8sCc96r
-8RMnV1eD55iqUFJLMguPkYBkq8DCtx81XcmAja93LvRR
-9aiGb2qTGB7xxrEWRrHtzgzBYTfq4y51hQGHrYxxJWna
-9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin
-ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL
-cndy3Z4yapfJBmL3ShUp5exZKqR3z33thTzeNMm2gRZ
-ComputeBudget111111111111111111111111111111
-CuieVDEDtLo7FypA9SbLM9saXFdb1dsshEkyErMqkRQq
-D3z8BLmMnPD1LaKwCkyCisM7iDyw9PsXXmvatUwjCuqT
-DtmE9D2CSB4L5D6A15mraeEjrGMm6auWVzgaD8hK2tZM
-DzpARSggzEgNc2HLdrMEguSf9KGPh5RG1NMBqNnF8ZuU
-F42dQ3SMssashRsA4SRfwJxFkGKV1bE3TcmpkagX8vvX
-FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH
-FZsgu4Gv9fn1iUm5v7iW3p9joX9HJcmxgXdRCqCGxpfE
-GCQ2KPaxKeweMdHcJtfdFd88o1mQvntA1oPSBkSfJwQp
-GDDMwNyyx8uB6zrqwBFHjLLG3TBYk2F8Az4yrQC5RzMp
-GVXRSBjFk6e6J3NbVPXohDJetcTjaeeuykUpbQF8UoMU
-H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG
-HfeFy4G9r77iyeXdbfNJjYw4z3NPEKDL6YQh3JzJ9s9f
-JBu1AL4obBcCMqKBBxhpWCNUt136ijcuMZLFvTP7iWdB
-JUP2jxvXaqu7NQY1GmNF4m1vodw12LVXYxbFL2uJvfo
-KeccakSecp256k11111111111111111111111111111
-MEisE1HzehtrDpAAT8PnLHjpSSkRYakotTuJRPjTpo8
-Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo
-MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr
-mv3ekLzLbnVPNxjSKvqBpU3ZeZXPQdEC3bp5MDEBG68
-PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY
-So1endDq2YkqhipRh3WViPa8hdiSpxWy6z3Z6tMCpAo
-SW1TCH7qEPTdLsDHRgPuMQjbQxKdH2aBStViMFnt64f
-TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
-Vote111111111111111111111111111111111111111
-whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc
-Y2akr3bXHRsqyP1QJtbm9G9N88ZV4t1KfaFeDzKRTfr
-ZETAxsqBRek56DhiGXrn75yj2NHU3aYUnxvHXpkf3aD
-Zo1ggzTUKMY5bYnDvT5mtVeZxzf2FaLTbKkmvGUhUQk
-";
+const DEFAULT_SCENARIO: &str = include_str!("../../scenarios/default.yaml");
 
 #[derive(Debug, Clone, Parser)]
 struct Args {
@@ -67,29 +62,1700 @@ struct Args {
     #[clap(short, long, default_value_t = String::from("ws://127.0.0.1:8000/streams"))]
     endpoint: String,
 
-    /// Path to file with accounts Pubkeys
+    /// TOML or YAML file with subscription groups, `scenarios/default.yaml` by default
     #[clap(long, short)]
-    accounts: Option<String>,
+    scenario: Option<String>,
 
-    /// Path to file with owners Pubkeys, by default some most used programs are used
-    #[clap(long, short)]
-    owners: Option<String>,
+    /// Expose Prometheus metrics on `http://<METRICS_ADDR>/metrics`, e.g. `127.0.0.1:9090`
+    #[clap(long)]
+    metrics_addr: Option<SocketAddr>,
+
+    /// Spread subscriptions of every group evenly over duration after group start delay,
+    /// e.g. `5m`, all at once by default
+    #[clap(long, value_parser = humantime::parse_duration)]
+    ramp_duration: Option<Duration>,
+
+    /// Max new connections per second, at least one per day, not limited by default
+    #[clap(long, value_parser = parse_connect_rate)]
+    connect_rate: Option<f64>,
+
+    /// Max number of connections, subscriptions which do not fit are skipped
+    #[clap(long)]
+    max_connections: Option<usize>,
+
+    /// Subscriptions per connection, all subscriptions share one connection by default
+    #[clap(long)]
+    subscriptions_per_connection: Option<usize>,
+
+    /// Write JSON report with per-group and per-subscription statistics on exit
+    #[clap(long)]
+    report: Option<String>,
+
+    /// Reconnect on disconnect and re-create failed subscriptions instead of stopping the test
+    #[clap(long, default_value_t = false)]
+    reconnect: bool,
+
+    /// Stop the test after duration, e.g. `10m`, runs until Ctrl+C by default
+    #[clap(long, value_parser = humantime::parse_duration)]
+    duration: Option<Duration>,
+
+    #[clap(flatten)]
+    thresholds: Thresholds,
+}
+
+/// Connect rate should be positive and interval between connects should fit
+/// into a day, too small rate overflows `Duration`
+fn parse_connect_rate(value: &str) -> Result<f64, String> {
+    let rate = value.parse::<f64>().map_err(|error| error.to_string())?;
+    match Duration::try_from_secs_f64(1.0 / rate) {
+        Ok(interval) if rate > 0.0 && interval <= Duration::from_secs(24 * 60 * 60) => Ok(rate),
+        _ => Err("should be positive and at least one connection per day".to_owned()),
+    }
+}
+
+/// Limits checked on exit, the test fails with non-zero exit code if any of them is not met
+#[derive(Debug, Clone, clap::Args)]
+#[clap(about = None, long_about = None, next_help_heading = "Thresholds")]
+struct Thresholds {
+    /// Fail if total messages per second is lower
+    #[clap(long)]
+    min_messages_per_sec: Option<f64>,
+
+    /// Fail if number of disconnects is higher
+    #[clap(long)]
+    max_disconnects: Option<u64>,
+
+    /// Fail if number of connect, subscribe and stream errors is higher
+    #[clap(long)]
+    max_errors: Option<u64>,
+
+    /// Fail if p99 of subscribe latency is higher, e.g. `500ms`
+    #[clap(long, value_parser = humantime::parse_duration)]
+    max_subscribe_latency_p99: Option<Duration>,
+
+    /// Fail if p99 of notification lag is higher, e.g. `400ms`, lag is measured from the first
+    /// receive of the slot by any subscription with the same commitment
+    #[clap(long, value_parser = humantime::parse_duration)]
+    max_lag_p99: Option<Duration>,
+}
+
+impl Thresholds {
+    /// Results of set thresholds, missing value (e.g. no successful subscriptions) fails
+    fn check(&self, totals: &Totals, connections: &ConnectionCounters) -> Vec<ThresholdSummary> {
+        let p99_ms = |histogram: &HdrHistogram<u64>| {
+            percentiles_ms(histogram).map(|percentiles| percentiles.p99)
+        };
+        let errors =
+            totals.errors.values().sum::<u64>() + connections.connect_errors.values().sum::<u64>();
+        let checks = [
+            (
+                "min_messages_per_sec",
+                self.min_messages_per_sec,
+                Some(totals.messages_per_sec),
+                false,
+            ),
+            (
+                "max_disconnects",
+                self.max_disconnects.map(|limit| limit as f64),
+                Some(connections.disconnects.values().sum::<u64>() as f64),
+                true,
+            ),
+            (
+                "max_errors",
+                self.max_errors.map(|limit| limit as f64),
+                Some(errors as f64),
+                true,
+            ),
+            (
+                "max_subscribe_latency_p99_ms",
+                self.max_subscribe_latency_p99
+                    .map(|limit| limit.as_secs_f64() * 1e3),
+                p99_ms(&totals.subscribe_latency),
+                true,
+            ),
+            (
+                "max_lag_p99_ms",
+                self.max_lag_p99.map(|limit| limit.as_secs_f64() * 1e3),
+                p99_ms(&totals.lag),
+                true,
+            ),
+        ];
+        checks
+            .into_iter()
+            .filter_map(|(name, limit, value, max)| {
+                limit.map(|limit| ThresholdSummary {
+                    name,
+                    limit,
+                    value,
+                    passed: match value {
+                        Some(value) if max => value <= limit,
+                        Some(value) => value >= limit,
+                        None => false,
+                    },
+                })
+            })
+            .collect()
+    }
+}
+
+/// Load profile for `--scenario`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Scenario {
+    groups: Vec<ScenarioGroup>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScenarioGroup {
+    name: String,
+    /// Number of subscriptions for every pubkey of the group
+    #[serde(default = "ScenarioGroup::default_count")]
+    count: usize,
+    /// Delay since the test start, e.g. `30s`
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    start_delay: Duration,
+    commitment: Option<ScenarioCommitment>,
+    #[serde(flatten)]
+    load: GroupLoad,
+}
+
+impl ScenarioGroup {
+    const fn default_count() -> usize {
+        1
+    }
+
+    /// Commitment of group notifications, `None` is the server default. Slot,
+    /// slot updates and votes are not confirmed yet, roots are finalized
+    const fn lag_commitment(&self) -> Option<ScenarioCommitment> {
+        match self.load {
+            GroupLoad::Slot {} | GroupLoad::SlotUpdate {} | GroupLoad::Vote {} => {
+                Some(ScenarioCommitment::Processed)
+            }
+            GroupLoad::Root {} => Some(ScenarioCommitment::Finalized),
+            _ => self.commitment,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case", deny_unknown_fields)]
+enum GroupLoad {
+    /// Subscription on account updates for every pubkey
+    Account {
+        #[serde(default, deserialize_with = "deserialize_pubkeys")]
+        pubkeys: Vec<Pubkey>,
+        /// File with pubkeys, one per line, relative to the scenario file
+        pubkeys_file: Option<String>,
+        encoding: Option<ScenarioAccountEncoding>,
+    },
+    /// Subscription on accounts updates owned by every pubkey
+    Program {
+        #[serde(default, deserialize_with = "deserialize_pubkeys")]
+        pubkeys: Vec<Pubkey>,
+        pubkeys_file: Option<String>,
+        encoding: Option<ScenarioAccountEncoding>,
+    },
+    /// Subscription on transactions logs which mention every pubkey,
+    /// all transactions without pubkeys
+    Logs {
+        #[serde(default, deserialize_with = "deserialize_pubkeys")]
+        pubkeys: Vec<Pubkey>,
+        pubkeys_file: Option<String>,
+        /// Include votes when subscribed on all transactions
+        #[serde(default)]
+        votes: bool,
+    },
+    Slot {},
+    SlotUpdate {},
+    Root {},
+    Vote {},
+    /// Experimental subscription on transactions
+    Transaction {
+        vote: Option<bool>,
+        failed: Option<bool>,
+        /// Transaction should include any of these accounts
+        #[serde(default, deserialize_with = "deserialize_pubkeys")]
+        account_include: Vec<Pubkey>,
+        /// Transaction should not contain any of these accounts
+        #[serde(default, deserialize_with = "deserialize_pubkeys")]
+        account_exclude: Vec<Pubkey>,
+        /// Transaction should contain all these accounts
+        #[serde(default, deserialize_with = "deserialize_pubkeys")]
+        account_required: Vec<Pubkey>,
+        encoding: Option<ScenarioTransactionEncoding>,
+        transaction_details: Option<ScenarioTransactionDetails>,
+        show_rewards: Option<bool>,
+        max_supported_transaction_version: Option<u8>,
+    },
+    /// Unstable subscription on blocks, all blocks without `mentions`
+    Block {
+        #[serde(default, deserialize_with = "deserialize_optional_pubkey")]
+        mentions: Option<Pubkey>,
+        encoding: Option<ScenarioTransactionEncoding>,
+        transaction_details: Option<ScenarioTransactionDetails>,
+        show_rewards: Option<bool>,
+        max_supported_transaction_version: Option<u8>,
+    },
+}
+
+impl GroupLoad {
+    const fn kind(&self) -> &'static str {
+        match self {
+            Self::Account { .. } => "account",
+            Self::Program { .. } => "program",
+            Self::Logs { .. } => "logs",
+            Self::Slot {} => "slot",
+            Self::SlotUpdate {} => "slot-update",
+            Self::Root {} => "root",
+            Self::Vote {} => "vote",
+            Self::Transaction { .. } => "transaction",
+            Self::Block { .. } => "block",
+        }
+    }
+
+    fn pubkeys(&self) -> &[Pubkey] {
+        match self {
+            Self::Account { pubkeys, .. }
+            | Self::Program { pubkeys, .. }
+            | Self::Logs { pubkeys, .. } => pubkeys,
+            Self::Slot {}
+            | Self::SlotUpdate {}
+            | Self::Root {}
+            | Self::Vote {}
+            | Self::Transaction { .. }
+            | Self::Block { .. } => &[],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum ScenarioCommitment {
+    Processed,
+    Confirmed,
+    Finalized,
+}
+
+impl From<ScenarioCommitment> for CommitmentConfig {
+    fn from(commitment: ScenarioCommitment) -> Self {
+        match commitment {
+            ScenarioCommitment::Processed => Self::processed(),
+            ScenarioCommitment::Confirmed => Self::confirmed(),
+            ScenarioCommitment::Finalized => Self::finalized(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum ScenarioAccountEncoding {
+    Binary,
+    Base58,
+    Base64,
+    JsonParsed,
+    Base64Zstd,
+}
+
+impl From<ScenarioAccountEncoding> for UiAccountEncoding {
+    fn from(encoding: ScenarioAccountEncoding) -> Self {
+        match encoding {
+            ScenarioAccountEncoding::Binary => Self::Binary,
+            ScenarioAccountEncoding::Base58 => Self::Base58,
+            ScenarioAccountEncoding::Base64 => Self::Base64,
+            ScenarioAccountEncoding::JsonParsed => Self::JsonParsed,
+            ScenarioAccountEncoding::Base64Zstd => Self::Base64Zstd,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum ScenarioTransactionEncoding {
+    Binary,
+    Base64,
+    Base58,
+    Json,
+    JsonParsed,
+}
+
+impl From<ScenarioTransactionEncoding> for UiTransactionEncoding {
+    fn from(encoding: ScenarioTransactionEncoding) -> Self {
+        match encoding {
+            ScenarioTransactionEncoding::Binary => Self::Binary,
+            ScenarioTransactionEncoding::Base64 => Self::Base64,
+            ScenarioTransactionEncoding::Base58 => Self::Base58,
+            ScenarioTransactionEncoding::Json => Self::Json,
+            ScenarioTransactionEncoding::JsonParsed => Self::JsonParsed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum ScenarioTransactionDetails {
+    Full,
+    Signatures,
+    None,
+    Accounts,
+}
+
+impl From<ScenarioTransactionDetails> for TransactionDetails {
+    fn from(details: ScenarioTransactionDetails) -> Self {
+        match details {
+            ScenarioTransactionDetails::Full => Self::Full,
+            ScenarioTransactionDetails::Signatures => Self::Signatures,
+            ScenarioTransactionDetails::None => Self::None,
+            ScenarioTransactionDetails::Accounts => Self::Accounts,
+        }
+    }
+}
+
+fn deserialize_duration<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    humantime::parse_duration(&value).map_err(serde::de::Error::custom)
+}
+
+fn deserialize_pubkeys<'de, D>(deserializer: D) -> Result<Vec<Pubkey>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|pubkey| {
+            Pubkey::from_str(pubkey)
+                .map_err(|_| serde::de::Error::custom(format!("invalid pubkey: {pubkey}")))
+        })
+        .collect()
+}
 
-    /// Additionally subscribe on transactions with both `accounts` and `owners`
-    #[clap(long, short, default_value_t = true)]
-    transactions: bool,
+fn deserialize_optional_pubkey<'de, D>(deserializer: D) -> Result<Option<Pubkey>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|pubkey| {
+            Pubkey::from_str(&pubkey)
+                .map_err(|_| serde::de::Error::custom(format!("invalid pubkey: {pubkey}")))
+        })
+        .transpose()
 }
 
-async fn load_pubkeys(path: Option<&str>, default: &'static str) -> anyhow::Result<Vec<Pubkey>> {
-    let data = if let Some(path) = path {
-        Cow::Owned(
-            fs::read_to_string(path)
+impl Scenario {
+    async fn load(path: Option<&str>) -> anyhow::Result<Self> {
+        let (mut scenario, dir): (Self, _) = match path {
+            Some(path) => {
+                let data = fs::read_to_string(path)
+                    .await
+                    .with_context(|| format!("failed to read scenario: {path}"))?;
+                let path = Path::new(path);
+                let scenario = match path.extension().and_then(|ext| ext.to_str()) {
+                    Some("toml") => toml::from_str(&data)?,
+                    Some("yaml" | "yml") => serde_yaml::from_str(&data)?,
+                    _ => anyhow::bail!(
+                        "unknown scenario format, expected toml or yaml: {}",
+                        path.display()
+                    ),
+                };
+                let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+                (scenario, dir)
+            }
+            None => (serde_yaml::from_str(DEFAULT_SCENARIO)?, PathBuf::new()),
+        };
+
+        anyhow::ensure!(
+            !scenario.groups.is_empty(),
+            "at least one group is required"
+        );
+        let mut names = HashSet::new();
+        for group in scenario.groups.iter_mut() {
+            anyhow::ensure!(
+                names.insert(group.name.clone()),
+                "group name is not unique: {}",
+                group.name
+            );
+            anyhow::ensure!(
+                group.count > 0,
+                "group {}: count should be positive",
+                group.name
+            );
+            match &mut group.load {
+                GroupLoad::Account {
+                    pubkeys,
+                    pubkeys_file,
+                    ..
+                }
+                | GroupLoad::Program {
+                    pubkeys,
+                    pubkeys_file,
+                    ..
+                }
+                | GroupLoad::Logs {
+                    pubkeys,
+                    pubkeys_file,
+                    ..
+                } => {
+                    if let Some(file) = pubkeys_file.take() {
+                        pubkeys.extend(load_pubkeys(&dir.join(file)).await?);
+                    }
+                }
+                GroupLoad::Slot {}
+                | GroupLoad::SlotUpdate {}
+                | GroupLoad::Root {}
+                | GroupLoad::Vote {}
+                | GroupLoad::Transaction { .. }
+                | GroupLoad::Block { .. } => {}
+            }
+            if matches!(
+                group.load,
+                GroupLoad::Account { .. } | GroupLoad::Program { .. }
+            ) {
+                anyhow::ensure!(
+                    !group.load.pubkeys().is_empty(),
+                    "group {}: at least one pubkey is required",
+                    group.name
+                );
+            }
+        }
+
+        Ok(scenario)
+    }
+
+    /// Groups and subscriptions of all groups ordered by start time
+    fn subscriptions(
+        self,
+        ramp_duration: Option<Duration>,
+    ) -> anyhow::Result<(Vec<Arc<ScenarioGroup>>, Vec<LoadSubscription>)> {
+        let mut groups = vec![];
+        let mut subscriptions = vec![];
+        for (group_index, group) in self.groups.into_iter().enumerate() {
+            let group = Arc::new(group);
+            groups.push(Arc::clone(&group));
+            let pubkeys = match group.load.pubkeys() {
+                [] => vec![None],
+                pubkeys => pubkeys.iter().copied().map(Some).collect(),
+            };
+            let total = pubkeys.len() * group.count;
+            for (index, pubkey) in (0..group.count)
+                .flat_map(|_| pubkeys.iter().copied())
+                .enumerate()
+            {
+                let ramp = ramp_duration.map_or(Duration::ZERO, |ramp_duration| {
+                    ramp_duration.mul_f64(index as f64 / total as f64)
+                });
+                subscriptions.push(LoadSubscription {
+                    start: group.start_delay + ramp,
+                    group: Arc::clone(&group),
+                    pubkey,
+                    counters: Arc::new(Mutex::new(SubscriptionCounters::new(group_index, pubkey)?)),
+                });
+            }
+        }
+        subscriptions.sort_by_key(|subscription| subscription.start);
+        Ok((groups, subscriptions))
+    }
+}
+
+#[derive(Debug)]
+struct Metrics {
+    registry: Registry,
+    /// Notifications by subscription kind
+    messages: IntCounterVec,
+    /// Payload of all data frames, counted only with `--metrics-addr`
+    received_bytes: IntCounter,
+    connect_latency: Histogram,
+    subscribe_latency: HistogramVec,
+    active_connections: IntGauge,
+    /// Connect, subscribe and stream errors by subscription kind and category
+    errors: IntCounterVec,
+    disconnects: IntCounterVec,
+    reconnect_downtime: Histogram,
+}
+
+impl Metrics {
+    fn new() -> anyhow::Result<Self> {
+        let registry = Registry::new_custom(Some("ws_stress".to_owned()), None)?;
+        let metrics = Self {
+            messages: IntCounterVec::new(
+                Opts::new(
+                    "messages_total",
+                    "Received notifications by subscription kind",
+                ),
+                &["kind"],
+            )?,
+            received_bytes: IntCounter::new(
+                "received_bytes_total",
+                "Received payload of WebSocket data frames",
+            )?,
+            connect_latency: Histogram::with_opts(HistogramOpts::new(
+                "connect_latency_seconds",
+                "Time to establish WebSocket connection",
+            ))?,
+            subscribe_latency: HistogramVec::new(
+                HistogramOpts::new(
+                    "subscribe_latency_seconds",
+                    "Time to confirm subscription by subscription kind",
+                ),
+                &["kind"],
+            )?,
+            active_connections: IntGauge::new("active_connections", "Open WebSocket connections")?,
+            errors: IntCounterVec::new(
+                Opts::new(
+                    "errors_total",
+                    "Connect, subscribe and stream errors by subscription kind and category",
+                ),
+                &["kind", "category"],
+            )?,
+            disconnects: IntCounterVec::new(
+                Opts::new("disconnects_total", "Disconnects by reason"),
+                &["reason"],
+            )?,
+            reconnect_downtime: Histogram::with_opts(HistogramOpts::new(
+                "reconnect_downtime_seconds",
+                "Time from disconnect to successful reconnect",
+            ))?,
+            registry,
+        };
+        metrics
+            .registry
+            .register(Box::new(metrics.messages.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.received_bytes.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.connect_latency.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.subscribe_latency.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.active_connections.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.errors.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.disconnects.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.reconnect_downtime.clone()))?;
+        Ok(metrics)
+    }
+
+    fn response(&self, request: &Request<Body>) -> Response<Body> {
+        if request.uri().path() != "/metrics" {
+            return Self::status(StatusCode::NOT_FOUND);
+        }
+
+        let encoder = TextEncoder::new();
+        let mut buffer = vec![];
+        if let Err(error) = encoder.encode(&self.registry.gather(), &mut buffer) {
+            tracing::error!("failed to encode metrics: {error}");
+            return Self::status(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+        Response::builder()
+            .header("content-type", encoder.format_type())
+            .body(Body::from(buffer))
+            .unwrap_or_else(|_| Self::status(StatusCode::INTERNAL_SERVER_ERROR))
+    }
+
+    fn status(status: StatusCode) -> Response<Body> {
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = status;
+        response
+    }
+}
+
+async fn serve_metrics(addr: SocketAddr, metrics: Arc<Metrics>) -> anyhow::Result<()> {
+    let make_service = make_service_fn(move |_conn| {
+        let metrics = Arc::clone(&metrics);
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                let response = metrics.response(&request);
+                async move { Ok::<_, Infallible>(response) }
+            }))
+        }
+    });
+    let server = Server::try_bind(&addr)
+        .with_context(|| format!("failed to bind metrics server: {addr}"))?;
+    tracing::info!("metrics server listening on http://{addr}/metrics");
+    server.serve(make_service).await?;
+    Ok(())
+}
+
+/// Frames are counted by clients, Prometheus counter is synced periodically
+async fn sync_received_bytes(
+    received_bytes: Arc<AtomicU64>,
+    metrics: Arc<Metrics>,
+) -> anyhow::Result<()> {
+    let mut sync = interval(Duration::from_secs(1));
+    loop {
+        sync.tick().await;
+        let received_bytes = received_bytes.load(Ordering::Relaxed);
+        metrics
+            .received_bytes
+            .inc_by(received_bytes.saturating_sub(metrics.received_bytes.get()));
+    }
+}
+
+async fn watch_events(
+    mut events: broadcast::Receiver<ConnectionEvent>,
+    ctx: Arc<LoadContext>,
+) -> anyhow::Result<()> {
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        };
+        let mut connections = ctx.lock_connections();
+        match event {
+            ConnectionEvent::Disconnected { reason } => {
+                ctx.metrics
+                    .disconnects
+                    .with_label_values(&[reason.as_str()])
+                    .inc();
+                ctx.metrics.active_connections.dec();
+                *connections.disconnects.entry(reason.as_str()).or_default() += 1;
+            }
+            ConnectionEvent::Reconnected { downtime, .. } => {
+                ctx.metrics.active_connections.inc();
+                ctx.metrics
+                    .reconnect_downtime
+                    .observe(downtime.as_secs_f64());
+                connections.reconnects += 1;
+                connections.downtime.saturating_record(as_micros(downtime));
+            }
+            ConnectionEvent::Gap { .. } => connections.gaps += 1,
+        }
+    }
+}
+
+type Task = BoxFuture<'static, anyhow::Result<()>>;
+
+#[derive(Debug)]
+struct LoadSubscription {
+    /// Offset since the test start
+    start: Duration,
+    group: Arc<ScenarioGroup>,
+    pubkey: Option<Pubkey>,
+    counters: Arc<Mutex<SubscriptionCounters>>,
+}
+
+impl LoadSubscription {
+    async fn run(self, client: WhirligigClient, ctx: Arc<LoadContext>) -> anyhow::Result<()> {
+        let name = &self.group.name;
+        let kind = self.group.load.kind();
+        let commitment = self.group.commitment.map(Into::into);
+        match (&self.group.load, self.pubkey) {
+            (GroupLoad::Account { encoding, .. }, Some(pubkey)) => {
+                run_subscription(
+                    &self.group,
+                    format!("{name}: subscribe on account updates: {pubkey}"),
+                    || {
+                        client.account_subscribe(
+                            &pubkey,
+                            Some(RpcAccountInfoConfig {
+                                encoding: encoding.map(Into::into),
+                                data_slice: None,
+                                commitment,
+                                min_context_slot: None,
+                            }),
+                        )
+                    },
+                    &ctx,
+                    &self.counters,
+                )
+                .await
+            }
+            (GroupLoad::Program { encoding, .. }, Some(pubkey)) => {
+                run_subscription(
+                    &self.group,
+                    format!("{name}: subscribe on program updates: {pubkey}"),
+                    || {
+                        client.program_subscribe(
+                            &pubkey,
+                            Some(RpcProgramAccountsConfig {
+                                filters: None,
+                                account_config: RpcAccountInfoConfig {
+                                    encoding: encoding.map(Into::into),
+                                    data_slice: None,
+                                    commitment,
+                                    min_context_slot: None,
+                                },
+                                with_context: None,
+                            }),
+                        )
+                    },
+                    &ctx,
+                    &self.counters,
+                )
+                .await
+            }
+            (GroupLoad::Account { .. } | GroupLoad::Program { .. }, None) => {
+                anyhow::bail!("{name}: pubkey is required for {kind}")
+            }
+            (GroupLoad::Logs { votes, .. }, pubkey) => {
+                let (filter, description) = match pubkey {
+                    Some(pubkey) => (
+                        RpcTransactionLogsFilter::Mentions(vec![pubkey.to_string()]),
+                        format!("{name}: subscribe on transactions for: {pubkey}"),
+                    ),
+                    None if *votes => (
+                        RpcTransactionLogsFilter::AllWithVotes,
+                        format!("{name}: subscribe on all transactions with votes"),
+                    ),
+                    None => (
+                        RpcTransactionLogsFilter::All,
+                        format!("{name}: subscribe on all transactions"),
+                    ),
+                };
+                run_subscription(
+                    &self.group,
+                    description,
+                    || {
+                        client
+                            .logs_subscribe(filter.clone(), RpcTransactionLogsConfig { commitment })
+                    },
+                    &ctx,
+                    &self.counters,
+                )
+                .await
+            }
+            (GroupLoad::Slot {}, _) => {
+                run_subscription(
+                    &self.group,
+                    format!("{name}: subscribe on slots"),
+                    || client.slot_subscribe(),
+                    &ctx,
+                    &self.counters,
+                )
+                .await
+            }
+            (GroupLoad::SlotUpdate {}, _) => {
+                run_subscription(
+                    &self.group,
+                    format!("{name}: subscribe on slots updates"),
+                    || client.slots_updates_subscribe(),
+                    &ctx,
+                    &self.counters,
+                )
+                .await
+            }
+            (GroupLoad::Root {}, _) => {
+                run_subscription(
+                    &self.group,
+                    format!("{name}: subscribe on roots"),
+                    || client.root_subscribe(),
+                    &ctx,
+                    &self.counters,
+                )
+                .await
+            }
+            (GroupLoad::Vote {}, _) => {
+                run_subscription(
+                    &self.group,
+                    format!("{name}: subscribe on votes"),
+                    || client.vote_subscribe(),
+                    &ctx,
+                    &self.counters,
+                )
                 .await
-                .with_context(|| format!("failed to read file: {path:?}"))?,
+            }
+            (
+                GroupLoad::Transaction {
+                    vote,
+                    failed,
+                    account_include,
+                    account_exclude,
+                    account_required,
+                    encoding,
+                    transaction_details,
+                    show_rewards,
+                    max_supported_transaction_version,
+                },
+                _,
+            ) => {
+                let to_strings =
+                    |pubkeys: &[Pubkey]| pubkeys.iter().map(Pubkey::to_string).collect();
+                run_subscription(
+                    &self.group,
+                    format!("{name}: subscribe on transactions"),
+                    || {
+                        client.transaction_subscribe(
+                            TransactionSubscribeFilter {
+                                vote: *vote,
+                                failed: *failed,
+                                signature: None,
+                                accounts: TransactionSubscribeFilterAccounts {
+                                    include: to_strings(account_include),
+                                    exclude: to_strings(account_exclude),
+                                    required: to_strings(account_required),
+                                },
+                            },
+                            TransactionSubscribeConfig {
+                                commitment,
+                                encoding: encoding.map(Into::into),
+                                transaction_details: transaction_details.map(Into::into),
+                                show_rewards: *show_rewards,
+                                max_supported_transaction_version:
+                                    *max_supported_transaction_version,
+                            },
+                        )
+                    },
+                    &ctx,
+                    &self.counters,
+                )
+                .await
+            }
+            (
+                GroupLoad::Block {
+                    mentions,
+                    encoding,
+                    transaction_details,
+                    show_rewards,
+                    max_supported_transaction_version,
+                },
+                _,
+            ) => {
+                let (filter, description) = match mentions {
+                    Some(pubkey) => (
+                        RpcBlockSubscribeFilter::MentionsAccountOrProgram(pubkey.to_string()),
+                        format!("{name}: subscribe on blocks for: {pubkey}"),
+                    ),
+                    None => (
+                        RpcBlockSubscribeFilter::All,
+                        format!("{name}: subscribe on blocks"),
+                    ),
+                };
+                run_subscription(
+                    &self.group,
+                    description,
+                    || {
+                        client.block_subscribe(
+                            filter.clone(),
+                            Some(RpcBlockSubscribeConfig {
+                                commitment,
+                                encoding: encoding.map(Into::into),
+                                transaction_details: transaction_details.map(Into::into),
+                                show_rewards: *show_rewards,
+                                max_supported_transaction_version:
+                                    *max_supported_transaction_version,
+                            }),
+                        )
+                    },
+                    &ctx,
+                    &self.counters,
+                )
+                .await
+            }
+        }
+    }
+}
+
+/// State shared by subscription tasks
+#[derive(Debug)]
+struct LoadContext {
+    pb: ProgressBar,
+    metrics: Arc<Metrics>,
+    slots: SlotsFirstSeen,
+    /// Re-create failed subscriptions with backoff, fail fast without it
+    reconnect: Option<ReconnectConfig>,
+    connections: Mutex<ConnectionCounters>,
+}
+
+impl LoadContext {
+    fn lock_connections(&self) -> MutexGuard<'_, ConnectionCounters> {
+        self.connections
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+
+    fn connect_failed(&self, error: &ClientError) {
+        self.metrics
+            .errors
+            .with_label_values(&["connect", error.as_str()])
+            .inc();
+        *self
+            .lock_connections()
+            .connect_errors
+            .entry(error.as_str())
+            .or_default() += 1;
+    }
+}
+
+/// Connection events of all connections
+#[derive(Debug)]
+struct ConnectionCounters {
+    disconnects: BTreeMap<&'static str, u64>,
+    reconnects: u64,
+    /// Reconnect downtime in microseconds
+    downtime: HdrHistogram<u64>,
+    /// Subscriptions re-issued after reconnect
+    gaps: u64,
+    connect_errors: BTreeMap<&'static str, u64>,
+}
+
+impl ConnectionCounters {
+    fn new() -> anyhow::Result<Self> {
+        Ok(Self {
+            disconnects: BTreeMap::new(),
+            reconnects: 0,
+            downtime: new_histogram()?,
+            gaps: 0,
+            connect_errors: BTreeMap::new(),
+        })
+    }
+
+    fn summary(&self) -> ConnectionsSummary {
+        ConnectionsSummary {
+            disconnects: self.disconnects.clone(),
+            reconnects: self.reconnects,
+            downtime_ms: percentiles_ms(&self.downtime),
+            gaps: self.gaps,
+            connect_errors: self.connect_errors.clone(),
+        }
+    }
+}
+
+/// First receive time of recent slots per commitment, used to measure
+/// notification lag: slot of `finalized` notification is compared only with
+/// other `finalized` notifications, not with `processed` ones seen ~13s earlier
+#[derive(Debug, Default)]
+struct SlotsFirstSeen(Mutex<HashMap<Option<ScenarioCommitment>, BTreeMap<Slot, Instant>>>);
+
+impl SlotsFirstSeen {
+    const RETENTION: usize = 512;
+
+    /// Time since the slot was seen first with the same commitment, see
+    /// [`ScenarioGroup::lag_commitment`]
+    fn lag(
+        &self,
+        commitment: Option<ScenarioCommitment>,
+        slot: Slot,
+        received: Instant,
+    ) -> Duration {
+        let mut commitments = self.0.lock().unwrap_or_else(|error| error.into_inner());
+        let slots = commitments.entry(commitment).or_default();
+        let first_seen = *slots.entry(slot).or_insert(received);
+        while slots.len() > Self::RETENTION {
+            slots.pop_first();
+        }
+        received.saturating_duration_since(first_seen)
+    }
+}
+
+/// Slot of notification, context slot for responses with context
+trait NotificationSlot {
+    fn slot(&self) -> Option<Slot>;
+}
+
+impl<T> NotificationSlot for RpcResponse<T> {
+    fn slot(&self) -> Option<Slot> {
+        Some(self.context.slot)
+    }
+}
+
+impl NotificationSlot for SlotInfo {
+    fn slot(&self) -> Option<Slot> {
+        Some(self.slot)
+    }
+}
+
+impl NotificationSlot for SlotUpdate {
+    fn slot(&self) -> Option<Slot> {
+        Some(SlotUpdate::slot(self))
+    }
+}
+
+impl NotificationSlot for Slot {
+    fn slot(&self) -> Option<Slot> {
+        Some(*self)
+    }
+}
+
+impl NotificationSlot for RpcVote {
+    fn slot(&self) -> Option<Slot> {
+        self.slots.last().copied()
+    }
+}
+
+impl NotificationSlot for TransactionNotification {
+    fn slot(&self) -> Option<Slot> {
+        Some(self.slot)
+    }
+}
+
+fn new_histogram() -> anyhow::Result<HdrHistogram<u64>> {
+    Ok(HdrHistogram::new_with_bounds(1, 60 * 1_000_000, 3)?)
+}
+
+fn as_micros(duration: Duration) -> u64 {
+    u64::try_from(duration.as_micros()).unwrap_or(u64::MAX)
+}
+
+/// Statistics of one subscription, updated by the subscription task
+#[derive(Debug)]
+struct SubscriptionCounters {
+    group: usize,
+    pubkey: Option<Pubkey>,
+    subscribed: Option<Instant>,
+    messages: u64,
+    /// Notification lag in microseconds
+    lag: HdrHistogram<u64>,
+    /// Subscribe latency of every subscribe in microseconds
+    subscribe_latency: HdrHistogram<u64>,
+    /// Received bytes and decode time of the current stream
+    stream: Option<Arc<SubscriptionStats>>,
+    /// Received bytes and decode time of failed streams
+    closed_bytes: u64,
+    closed_decode_time: Duration,
+    errors: BTreeMap<&'static str, u64>,
+    resubscribes: u64,
+}
+
+impl SubscriptionCounters {
+    fn new(group: usize, pubkey: Option<Pubkey>) -> anyhow::Result<Self> {
+        Ok(Self {
+            group,
+            pubkey,
+            subscribed: None,
+            messages: 0,
+            lag: new_histogram()?,
+            subscribe_latency: new_histogram()?,
+            stream: None,
+            closed_bytes: 0,
+            closed_decode_time: Duration::ZERO,
+            errors: BTreeMap::new(),
+            resubscribes: 0,
+        })
+    }
+
+    fn set_stream(&mut self, stream: Arc<SubscriptionStats>) {
+        if let Some(closed) = self.stream.replace(stream) {
+            self.closed_bytes += closed.bytes();
+            self.closed_decode_time += closed.decode_time();
+        }
+    }
+
+    fn bytes(&self) -> u64 {
+        self.closed_bytes + self.stream.as_ref().map_or(0, |stream| stream.bytes())
+    }
+
+    fn decode_time(&self) -> Duration {
+        self.closed_decode_time
+            + self
+                .stream
+                .as_ref()
+                .map_or(Duration::ZERO, |stream| stream.decode_time())
+    }
+
+    fn lock(counters: &Mutex<Self>) -> MutexGuard<'_, Self> {
+        counters.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+/// Sum of subscriptions statistics, rates are averaged since subscribe
+#[derive(Debug)]
+struct Totals {
+    subscriptions: usize,
+    subscribed: usize,
+    messages: u64,
+    bytes: u64,
+    decode_time: Duration,
+    lag: HdrHistogram<u64>,
+    subscribe_latency: HdrHistogram<u64>,
+    messages_per_sec: f64,
+    bytes_per_sec: f64,
+    errors: BTreeMap<&'static str, u64>,
+    resubscribes: u64,
+}
+
+impl Totals {
+    fn new() -> anyhow::Result<Self> {
+        Ok(Self {
+            subscriptions: 0,
+            subscribed: 0,
+            messages: 0,
+            bytes: 0,
+            decode_time: Duration::ZERO,
+            lag: new_histogram()?,
+            subscribe_latency: new_histogram()?,
+            messages_per_sec: 0.0,
+            bytes_per_sec: 0.0,
+            errors: BTreeMap::new(),
+            resubscribes: 0,
+        })
+    }
+
+    fn add(&mut self, counters: &SubscriptionCounters, now: Instant) -> anyhow::Result<()> {
+        let bytes = counters.bytes();
+        self.subscriptions += 1;
+        self.messages += counters.messages;
+        self.bytes += bytes;
+        self.decode_time += counters.decode_time();
+        self.lag.add(&counters.lag)?;
+        self.subscribe_latency.add(&counters.subscribe_latency)?;
+        for (category, count) in counters.errors.iter() {
+            *self.errors.entry(category).or_default() += count;
+        }
+        self.resubscribes += counters.resubscribes;
+        if let Some(subscribed) = counters.subscribed {
+            self.subscribed += 1;
+            let elapsed = now.saturating_duration_since(subscribed).as_secs_f64();
+            if elapsed > 0.0 {
+                self.messages_per_sec += counters.messages as f64 / elapsed;
+                self.bytes_per_sec += bytes as f64 / elapsed;
+            }
+        }
+        Ok(())
+    }
+
+    fn decode_avg(&self) -> Option<Duration> {
+        u32::try_from(self.messages)
+            .ok()
+            .filter(|messages| *messages > 0)
+            .map(|messages| self.decode_time / messages)
+    }
+
+    fn summary(&self) -> ThroughputSummary {
+        ThroughputSummary {
+            messages: self.messages,
+            bytes: self.bytes,
+            messages_per_sec: self.messages_per_sec,
+            bytes_per_sec: self.bytes_per_sec,
+            decode_avg_us: self
+                .decode_avg()
+                .map(|decode_avg| decode_avg.as_secs_f64() * 1e6),
+            lag_ms: percentiles_ms(&self.lag),
+            subscribe_latency_ms: percentiles_ms(&self.subscribe_latency),
+            errors: self.errors.clone(),
+            resubscribes: self.resubscribes,
+        }
+    }
+}
+
+/// Percentiles of histogram with microseconds in milliseconds
+fn percentiles_ms(histogram: &HdrHistogram<u64>) -> Option<PercentilesSummary> {
+    (!histogram.is_empty()).then(|| {
+        let quantile = |quantile| histogram.value_at_quantile(quantile) as f64 / 1e3;
+        PercentilesSummary {
+            p50: quantile(0.5),
+            p90: quantile(0.9),
+            p99: quantile(0.99),
+            max: histogram.max() as f64 / 1e3,
+        }
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct ThroughputSummary {
+    messages: u64,
+    bytes: u64,
+    messages_per_sec: f64,
+    bytes_per_sec: f64,
+    decode_avg_us: Option<f64>,
+    lag_ms: Option<PercentilesSummary>,
+    subscribe_latency_ms: Option<PercentilesSummary>,
+    errors: BTreeMap<&'static str, u64>,
+    resubscribes: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct PercentilesSummary {
+    p50: f64,
+    p90: f64,
+    p99: f64,
+    max: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonReport {
+    elapsed_secs: f64,
+    /// All thresholds passed
+    passed: bool,
+    thresholds: Vec<ThresholdSummary>,
+    connections: ConnectionsSummary,
+    groups: Vec<GroupSummary>,
+    subscriptions: Vec<SubscriptionSummary>,
+}
+
+#[derive(Debug, Serialize)]
+struct ThresholdSummary {
+    name: &'static str,
+    limit: f64,
+    value: Option<f64>,
+    passed: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct ConnectionsSummary {
+    disconnects: BTreeMap<&'static str, u64>,
+    reconnects: u64,
+    downtime_ms: Option<PercentilesSummary>,
+    gaps: u64,
+    connect_errors: BTreeMap<&'static str, u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct GroupSummary {
+    name: String,
+    kind: &'static str,
+    subscriptions: usize,
+    subscribed: usize,
+    #[serde(flatten)]
+    throughput: ThroughputSummary,
+}
+
+#[derive(Debug, Serialize)]
+struct SubscriptionSummary {
+    group: String,
+    kind: &'static str,
+    pubkey: Option<String>,
+    subscribed: bool,
+    #[serde(flatten)]
+    throughput: ThroughputSummary,
+}
+
+/// Per-group live table and final report
+#[derive(Debug)]
+struct Report {
+    started: Instant,
+    ctx: Arc<LoadContext>,
+    groups: Vec<Arc<ScenarioGroup>>,
+    subscriptions: Vec<Arc<Mutex<SubscriptionCounters>>>,
+    /// Messages and bytes of every group on previous table update
+    last_update: Instant,
+    last_totals: Vec<(u64, u64)>,
+}
+
+impl Report {
+    fn new(
+        ctx: Arc<LoadContext>,
+        groups: Vec<Arc<ScenarioGroup>>,
+        subscriptions: &[LoadSubscription],
+    ) -> Self {
+        let now = Instant::now();
+        Self {
+            started: now,
+            ctx,
+            last_totals: vec![(0, 0); groups.len()],
+            groups,
+            subscriptions: subscriptions
+                .iter()
+                .map(|subscription| Arc::clone(&subscription.counters))
+                .collect(),
+            last_update: now,
+        }
+    }
+
+    fn group_totals(&self, now: Instant) -> anyhow::Result<Vec<Totals>> {
+        let mut totals = self
+            .groups
+            .iter()
+            .map(|_| Totals::new())
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        for counters in self.subscriptions.iter() {
+            let counters = SubscriptionCounters::lock(counters);
+            totals[counters.group].add(&counters, now)?;
+        }
+        Ok(totals)
+    }
+
+    fn check_thresholds(&self, thresholds: &Thresholds) -> anyhow::Result<Vec<ThresholdSummary>> {
+        let now = Instant::now();
+        let mut totals = Totals::new()?;
+        for counters in self.subscriptions.iter() {
+            totals.add(&SubscriptionCounters::lock(counters), now)?;
+        }
+        Ok(thresholds.check(&totals, &self.ctx.lock_connections()))
+    }
+
+    /// Table with rates since previous update
+    fn live_table(&mut self) -> anyhow::Result<String> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_update).as_secs_f64();
+        let mut totals = self.group_totals(now)?;
+        for (totals, last_totals) in totals.iter_mut().zip(self.last_totals.iter_mut()) {
+            if elapsed > 0.0 {
+                totals.messages_per_sec =
+                    totals.messages.saturating_sub(last_totals.0) as f64 / elapsed;
+                totals.bytes_per_sec = totals.bytes.saturating_sub(last_totals.1) as f64 / elapsed;
+            }
+            *last_totals = (totals.messages, totals.bytes);
+        }
+        self.last_update = now;
+        Ok(self.format_table(&totals))
+    }
+
+    fn format_table(&self, totals: &[Totals]) -> String {
+        let width = self
+            .groups
+            .iter()
+            .map(|group| group.name.len())
+            .max()
+            .unwrap_or(0)
+            .max(10);
+        let mut lines = vec![format!(
+            "{:<width$} {:<12} {:>9} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>8}",
+            "group",
+            "kind",
+            "subs",
+            "msg/s",
+            "KiB/s",
+            "messages",
+            "decode",
+            "lag p50",
+            "lag p99",
+            "errors"
+        )];
+        for (group, totals) in self.groups.iter().zip(totals.iter()) {
+            let quantile = |quantile| {
+                if totals.lag.is_empty() {
+                    "-".to_owned()
+                } else {
+                    format!(
+                        "{:?}",
+                        Duration::from_micros(totals.lag.value_at_quantile(quantile))
+                    )
+                }
+            };
+            lines.push(format!(
+                "{:<width$} {:<12} {:>9} {:>10.1} {:>10.1} {:>10} {:>10} {:>10} {:>10} {:>8}",
+                group.name,
+                group.load.kind(),
+                format!("{}/{}", totals.subscribed, totals.subscriptions),
+                totals.messages_per_sec,
+                totals.bytes_per_sec / 1024.0,
+                totals.messages,
+                totals
+                    .decode_avg()
+                    .map_or_else(|| "-".to_owned(), |decode_avg| format!("{decode_avg:?}")),
+                quantile(0.5),
+                quantile(0.99),
+                totals.errors.values().sum::<u64>(),
+            ));
+        }
+        lines.join("\n")
+    }
+
+    fn print_summary(&self, thresholds: &[ThresholdSummary]) -> anyhow::Result<()> {
+        let totals = self.group_totals(Instant::now())?;
+        println!(
+            "summary: {} subscriptions in {}",
+            self.subscriptions.len(),
+            humantime::format_duration(Duration::from_secs(self.started.elapsed().as_secs()))
+        );
+        println!("{}", self.format_table(&totals));
+
+        let connections = self.ctx.lock_connections();
+        let format_counts = |counts: &BTreeMap<&str, u64>| {
+            counts
+                .iter()
+                .map(|(name, count)| format!("{name}: {count}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        println!(
+            "connections: {} disconnects ({}), {} reconnects, {} gaps",
+            connections.disconnects.values().sum::<u64>(),
+            format_counts(&connections.disconnects),
+            connections.reconnects,
+            connections.gaps,
+        );
+        if !connections.downtime.is_empty() {
+            println!(
+                "reconnect downtime: p50 {:?}, max {:?}",
+                Duration::from_micros(connections.downtime.value_at_quantile(0.5)),
+                Duration::from_micros(connections.downtime.max()),
+            );
+        }
+        if !connections.connect_errors.is_empty() {
+            println!(
+                "connect errors: {}",
+                format_counts(&connections.connect_errors)
+            );
+        }
+        let mut errors = BTreeMap::new();
+        for totals in totals.iter() {
+            for (category, count) in totals.errors.iter() {
+                *errors.entry(*category).or_default() += count;
+            }
+        }
+        if !errors.is_empty() {
+            println!("errors: {}", format_counts(&errors));
+        }
+        for threshold in thresholds.iter() {
+            println!(
+                "threshold {}: {} (limit {}), {}",
+                threshold.name,
+                threshold
+                    .value
+                    .map_or_else(|| "-".to_owned(), |value| format!("{value:.1}")),
+                threshold.limit,
+                if threshold.passed { "passed" } else { "failed" }
+            );
+        }
+        Ok(())
+    }
+
+    fn write_json(&self, path: &str, thresholds: Vec<ThresholdSummary>) -> anyhow::Result<()> {
+        let now = Instant::now();
+        let groups = self
+            .groups
+            .iter()
+            .zip(self.group_totals(now)?)
+            .map(|(group, totals)| GroupSummary {
+                name: group.name.clone(),
+                kind: group.load.kind(),
+                subscriptions: totals.subscriptions,
+                subscribed: totals.subscribed,
+                throughput: totals.summary(),
+            })
+            .collect();
+        let subscriptions = self
+            .subscriptions
+            .iter()
+            .map(|counters| {
+                let counters = SubscriptionCounters::lock(counters);
+                let group = &self.groups[counters.group];
+                let mut totals = Totals::new()?;
+                totals.add(&counters, now)?;
+                Ok(SubscriptionSummary {
+                    group: group.name.clone(),
+                    kind: group.load.kind(),
+                    pubkey: counters.pubkey.map(|pubkey| pubkey.to_string()),
+                    subscribed: counters.subscribed.is_some(),
+                    throughput: totals.summary(),
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        let report = JsonReport {
+            elapsed_secs: now.duration_since(self.started).as_secs_f64(),
+            passed: thresholds.iter().all(|threshold| threshold.passed),
+            thresholds,
+            connections: self.ctx.lock_connections().summary(),
+            groups,
+            subscriptions,
+        };
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("failed to create report: {path}"))?;
+        serde_json::to_writer_pretty(file, &report)?;
+        Ok(())
+    }
+}
+
+/// Opens connections and creates subscriptions at their start time with
+/// connection limits, tasks are sent to the main loop to fail fast on errors
+/// unless `--reconnect` is used
+struct Scheduler {
+    endpoint: String,
+    config: ClientConfig,
+    connect_interval: Option<Duration>,
+    max_connections: Option<usize>,
+    subscriptions_per_connection: Option<usize>,
+    tasks_tx: mpsc::UnboundedSender<Task>,
+    ctx: Arc<LoadContext>,
+}
+
+impl Scheduler {
+    async fn run(self, subscriptions: Vec<LoadSubscription>) -> anyhow::Result<()> {
+        let started = Instant::now();
+        let total = subscriptions.len();
+        let mut client = None;
+        let mut connections = 0;
+        let mut on_connection = 0;
+        let mut next_connect = started;
+        for (index, subscription) in subscriptions.into_iter().enumerate() {
+            sleep_until(started + subscription.start).await;
+
+            let is_full = self
+                .subscriptions_per_connection
+                .is_some_and(|max| on_connection >= max);
+            let current = client.as_ref().filter(|_| !is_full).cloned();
+            let current = match current {
+                Some(client) => client,
+                None => {
+                    if self.max_connections.is_some_and(|max| connections >= max) {
+                        tracing::warn!(
+                            "max connections reached, {} subscriptions skipped",
+                            total - index
+                        );
+                        break;
+                    }
+                    sleep_until(next_connect).await;
+                    if let Some(connect_interval) = self.connect_interval {
+                        next_connect = Instant::now() + connect_interval;
+                    }
+
+                    let new_client = self.connect().await?;
+                    connections += 1;
+                    on_connection = 0;
+                    client = Some(new_client.clone());
+                    new_client
+                }
+            };
+
+            on_connection += 1;
+            self.spawn(subscription.run(current, Arc::clone(&self.ctx)).boxed())?;
+            self.ctx.pb.set_message(format!(
+                "{connections} connections, {}/{total} subscriptions",
+                index + 1
+            ));
+        }
+        Ok(())
+    }
+
+    async fn connect(&self) -> anyhow::Result<WhirligigClient> {
+        let mut attempts = 0;
+        loop {
+            let ts = Instant::now();
+            let error =
+                match WhirligigClient::connect_with_config(&self.endpoint, self.config.clone())
+                    .await
+                {
+                    Ok(client) => {
+                        self.ctx
+                            .metrics
+                            .connect_latency
+                            .observe(ts.elapsed().as_secs_f64());
+                        self.ctx.metrics.active_connections.inc();
+                        self.spawn(
+                            watch_events(client.subscribe_events(), Arc::clone(&self.ctx)).boxed(),
+                        )?;
+                        return Ok(client);
+                    }
+                    Err(error) => error,
+                };
+
+            self.ctx.connect_failed(&error);
+            let Some(reconnect) = &self.ctx.reconnect else {
+                return Err(error.into());
+            };
+            attempts += 1;
+            if matches!(reconnect.max_attempts, Some(max) if attempts >= max) {
+                return Err(error.into());
+            }
+            tracing::warn!("failed to connect, attempt #{attempts}: {error}");
+            sleep(reconnect.backoff(attempts)).await;
+        }
+    }
+
+    fn spawn(&self, task: Task) -> anyhow::Result<()> {
+        self.tasks_tx
+            .unbounded_send(task)
+            .map_err(|_| anyhow::anyhow!("main loop is closed"))
+    }
+}
+
+/// Subscribe with measured latency and count notifications until the stream
+/// fails, with `--reconnect` failed subscription is re-created after backoff
+async fn run_subscription<T, F>(
+    group: &ScenarioGroup,
+    description: String,
+    subscribe: impl Fn() -> F,
+    ctx: &LoadContext,
+    counters: &Mutex<SubscriptionCounters>,
+) -> anyhow::Result<()>
+where
+    T: NotificationSlot,
+    F: std::future::Future<Output = ClientResult<Subscription<T>>>,
+{
+    let kind = group.load.kind();
+    let mut attempts = 0;
+    loop {
+        let (category, error) = consume_subscription(
+            group,
+            &description,
+            subscribe(),
+            ctx,
+            counters,
+            &mut attempts,
         )
-    } else {
-        Cow::Borrowed(default)
+        .await;
+        ctx.metrics
+            .errors
+            .with_label_values(&[kind, category])
+            .inc();
+        *SubscriptionCounters::lock(counters)
+            .errors
+            .entry(category)
+            .or_default() += 1;
+
+        let Some(reconnect) = &ctx.reconnect else {
+            return Err(error);
+        };
+        attempts += 1;
+        tracing::debug!("{description}: {error}, attempt #{attempts}");
+        sleep(reconnect.backoff(attempts)).await;
+    }
+}
+
+/// Returns error category and error once subscription failed
+async fn consume_subscription<T: NotificationSlot>(
+    group: &ScenarioGroup,
+    description: &str,
+    subscribe: impl std::future::Future<Output = ClientResult<Subscription<T>>>,
+    ctx: &LoadContext,
+    counters: &Mutex<SubscriptionCounters>,
+    attempts: &mut u32,
+) -> (&'static str, anyhow::Error) {
+    let kind = group.load.kind();
+    let lag_commitment = group.lag_commitment();
+    let ts = Instant::now();
+    let mut stream = match subscribe.await {
+        Ok(stream) => stream,
+        Err(error) => return (error.as_str(), error.into()),
     };
+    *attempts = 0;
+    let latency = ts.elapsed();
+    ctx.metrics
+        .subscribe_latency
+        .with_label_values(&[kind])
+        .observe(latency.as_secs_f64());
+    {
+        let mut counters = SubscriptionCounters::lock(counters);
+        counters
+            .subscribe_latency
+            .saturating_record(as_micros(latency));
+        if counters.subscribed.is_none() {
+            counters.subscribed = Some(Instant::now());
+            ctx.pb.println(description);
+        } else {
+            counters.resubscribes += 1;
+        }
+        counters.set_stream(stream.stats());
+    }
+
+    let messages = ctx.metrics.messages.with_label_values(&[kind]);
+    while let Some(item) = stream.next().await {
+        let item = match item {
+            Ok(item) => item,
+            Err(error) => return (error.as_str(), error.into()),
+        };
+        let lag = item
+            .slot()
+            .map(|slot| ctx.slots.lag(lag_commitment, slot, Instant::now()));
+        messages.inc();
+        ctx.pb.inc(1);
+
+        let mut counters = SubscriptionCounters::lock(counters);
+        counters.messages += 1;
+        if let Some(lag) = lag {
+            counters.lag.saturating_record(as_micros(lag));
+        }
+    }
+    ("stream_finished", anyhow::anyhow!("stream finished"))
+}
+
+async fn load_pubkeys(path: &Path) -> anyhow::Result<Vec<Pubkey>> {
+    let data = fs::read_to_string(path)
+        .await
+        .with_context(|| format!("failed to read file: {path:?}"))?;
 
     data.split('\n')
         .filter_map(|mut line| {
@@ -101,7 +1767,7 @@ async fn load_pubkeys(path: Option<&str>, default: &'static str) -> anyhow::Resu
             }
         })
         .collect::<Result<Vec<Pubkey>, _>>()
-        .map_err(Into::into)
+        .with_context(|| format!("invalid pubkey in file: {path:?}"))
 }
 
 #[tokio::main]
@@ -110,77 +1776,120 @@ async fn main() -> anyhow::Result<()> {
 
     let args = Args::parse();
 
-    let accounts = load_pubkeys(args.accounts.as_deref(), ACCOUNTS).await?;
-    let owners = load_pubkeys(args.owners.as_deref(), OWNERS).await?;
+    let scenario = Scenario::load(args.scenario.as_deref()).await?;
+    anyhow::ensure!(
+        args.max_connections != Some(0),
+        "--max-connections should be positive"
+    );
+    anyhow::ensure!(
+        args.subscriptions_per_connection != Some(0),
+        "--subscriptions-per-connection should be positive"
+    );
 
+    let metrics = Arc::new(Metrics::new()?);
     let mut tasks = JoinSet::new();
-    let pb = Arc::new(ProgressBar::new(u64::MAX));
+    let mut config = ClientConfig {
+        ping_interval: args.reconnect.then_some(Duration::from_secs(10)),
+        reconnect: args.reconnect.then(ReconnectConfig::default),
+        ..Default::default()
+    };
+    if let Some(addr) = args.metrics_addr {
+        let received_bytes = Arc::new(AtomicU64::new(0));
+        config.received_bytes = Some(Arc::clone(&received_bytes));
+        tasks.spawn(sync_received_bytes(received_bytes, Arc::clone(&metrics)));
+        tasks.spawn(serve_metrics(addr, Arc::clone(&metrics)));
+    }
+
+    let multi_progress = MultiProgress::new();
+    let pb = multi_progress.add(ProgressBar::new(u64::MAX));
     pb.set_style(ProgressStyle::with_template(
-        "{spinner:.green} +{pos} messages",
+        "{spinner:.green} +{pos} messages, {msg}",
     )?);
+    let table = multi_progress.add(ProgressBar::new(0));
+    table.set_style(ProgressStyle::with_template("{msg}")?);
 
-    #[allow(clippy::unnecessary_to_owned)]
-    for pubkey in accounts.iter().cloned() {
-        let endpoint = args.endpoint.clone();
-        let pb = Arc::clone(&pb);
-        tasks.spawn(async move {
-            let client = PubsubClient::new(&endpoint).await?;
-            let (mut stream, _unsubscribe) = client.account_subscribe(&pubkey, None).await?;
-            pb.println(format!("subscribe on accout updates: {pubkey}"));
-            while stream.next().await.is_some() {
-                pb.inc(1);
-            }
-            anyhow::bail!("stream finished");
-        });
-    }
-
-    #[allow(clippy::unnecessary_to_owned)]
-    for pubkey in owners.iter().cloned() {
-        let endpoint = args.endpoint.clone();
-        let pb = Arc::clone(&pb);
-        tasks.spawn(async move {
-            let client = PubsubClient::new(&endpoint).await?;
-            let (mut stream, _unsubscribe) = client.program_subscribe(&pubkey, None).await?;
-            pb.println(format!("subscribe on program updates: {pubkey}"));
-            while stream.next().await.is_some() {
-                pb.inc(1);
-            }
-            anyhow::bail!("stream finished");
-        });
-    }
-
-    if args.transactions {
-        for pubkey in accounts.into_iter().chain(owners.into_iter()) {
-            let endpoint = args.endpoint.clone();
-            let pb = Arc::clone(&pb);
-            tasks.spawn(async move {
-                let client = PubsubClient::new(&endpoint).await?;
-                let (mut stream, _ubsubscribe) = client
-                    .logs_subscribe(
-                        RpcTransactionLogsFilter::Mentions(vec![pubkey.to_string()]),
-                        RpcTransactionLogsConfig { commitment: None },
-                    )
-                    .await?;
-                pb.println(format!("subscribe on transactions for: {pubkey}"));
-                while stream.next().await.is_some() {
-                    pb.inc(1);
-                }
-                anyhow::bail!("stream finished");
-            });
-        }
+    let ctx = Arc::new(LoadContext {
+        pb: pb.clone(),
+        metrics,
+        slots: SlotsFirstSeen::default(),
+        reconnect: config.reconnect.clone(),
+        connections: Mutex::new(ConnectionCounters::new()?),
+    });
+    let (groups, subscriptions) = scenario.subscriptions(args.ramp_duration)?;
+    let mut report = Report::new(Arc::clone(&ctx), groups, &subscriptions);
+
+    let (tasks_tx, mut tasks_rx) = mpsc::unbounded();
+    let scheduler = Scheduler {
+        endpoint: args.endpoint,
+        config,
+        connect_interval: args
+            .connect_rate
+            .map(|rate| Duration::from_secs_f64(1.0 / rate)),
+        max_connections: args.max_connections,
+        subscriptions_per_connection: args.subscriptions_per_connection,
+        tasks_tx,
+        ctx,
     }
+    .run(subscriptions);
+    tokio::pin!(scheduler);
+    let ctrl_c = signal::ctrl_c();
+    tokio::pin!(ctrl_c);
 
-    loop {
-        tokio::select! {
+    let deadline = sleep(args.duration.unwrap_or(Duration::MAX));
+    tokio::pin!(deadline);
+
+    let mut table_interval = interval(Duration::from_secs(1));
+    let mut scheduled = false;
+    let mut received_all_tasks = false;
+    let result = loop {
+        let result: anyhow::Result<()> = tokio::select! {
             _ = sleep(Duration::from_millis(10)) => {
                 pb.tick();
+                Ok(())
             }
-            result = tasks.join_next() => match result {
-                Some(result) => result??,
-                None => break,
+            _ = table_interval.tick() => report.live_table().map(|text| table.set_message(text)),
+            result = &mut ctrl_c => match result {
+                Ok(()) => break Ok(()),
+                Err(error) => Err(error.into()),
+            },
+            () = &mut deadline, if args.duration.is_some() => break Ok(()),
+            result = &mut scheduler, if !scheduled => {
+                scheduled = true;
+                result
             }
+            task = tasks_rx.next(), if !received_all_tasks => {
+                match task {
+                    Some(task) => {
+                        tasks.spawn(task);
+                    }
+                    None => received_all_tasks = true,
+                }
+                Ok(())
+            }
+            Some(result) = tasks.join_next() => result.unwrap_or_else(|error| Err(error.into())),
+        };
+        if result.is_err() || (received_all_tasks && tasks.is_empty()) {
+            break result;
         }
-    }
+    };
 
+    table.finish_and_clear();
+    pb.finish_and_clear();
+    let thresholds = report.check_thresholds(&args.thresholds)?;
+    let failed = thresholds
+        .iter()
+        .filter(|threshold| !threshold.passed)
+        .map(|threshold| threshold.name)
+        .collect::<Vec<_>>();
+    report.print_summary(&thresholds)?;
+    if let Some(path) = &args.report {
+        report.write_json(path, thresholds)?;
+    }
+    result?;
+    anyhow::ensure!(
+        failed.is_empty(),
+        "failed thresholds: {}",
+        failed.join(", ")
+    );
     Ok(())
 }
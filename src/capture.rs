@@ -0,0 +1,324 @@
+//! Append-only capture of raw WebSocket frames.
+//!
+//! File starts with [`MAGIC`], followed by records:
+//!
+//! | field      | type   | description                                           |
+//! |------------|--------|-------------------------------------------------------|
+//! | kind       | u8     | `1` text, `2` binary, `3` session, `4` subscription   |
+//! | monotonic  | u64 LE | nanoseconds since the process start                   |
+//! | wall clock | u64 LE | nanoseconds since UNIX epoch                          |
+//! | length     | u32 LE | payload length                                        |
+//! | payload    | bytes  | frame payload, JSON subscription, empty for session   |
+//!
+//! Every [`CaptureWriter::open`] starts a new session with an empty session
+//! start record, monotonic time is comparable only within one session.
+//! Subscription record is written by the client once subscribe request is
+//! acknowledged, it maps server side id to the subscribe request. Whole
+//! file can be compressed with zstd, new sessions appended to the existing
+//! capture are written as separate zstd frames, compression of appended
+//! session should match the existing capture.
+
+use {
+    serde::{Deserialize, Serialize},
+    serde_json::Value,
+    std::{
+        fs::{File, OpenOptions},
+        io::{self, BufReader, BufWriter, Read, Write},
+        path::Path,
+        sync::OnceLock,
+        time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    },
+    tokio_tungstenite::tungstenite::protocol::Message,
+};
+
+pub const MAGIC: &[u8; 8] = b"WLGCAP\x00\x01";
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+const KIND_TEXT: u8 = 1;
+const KIND_BINARY: u8 = 2;
+const KIND_SESSION: u8 = 3;
+const KIND_SUBSCRIPTION: u8 = 4;
+
+/// Monotonic time since the first call in the process, shared by all
+/// connections so records from different connections can be ordered
+pub fn monotonic_now() -> Duration {
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaptureFrame {
+    Text(String),
+    Binary(Vec<u8>),
+    /// Subscription created by the client, not a received frame
+    Subscription(CaptureSubscription),
+}
+
+impl TryFrom<CaptureFrame> for Message {
+    type Error = CaptureFrame;
+
+    fn try_from(frame: CaptureFrame) -> Result<Self, Self::Error> {
+        match frame {
+            CaptureFrame::Text(data) => Ok(Self::Text(data)),
+            CaptureFrame::Binary(data) => Ok(Self::Binary(data)),
+            frame @ CaptureFrame::Subscription(_) => Err(frame),
+        }
+    }
+}
+
+/// Subscribe request with server side id from the response
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureSubscription {
+    /// Client side id, the same for subscription re-issued after reconnect
+    pub id: u64,
+    pub method: String,
+    pub params: Value,
+    /// Server side id used in notifications
+    pub subscription: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureRecord {
+    /// Index of the session in the capture, set by [`CaptureReader`]
+    pub session: u32,
+    pub monotonic: Duration,
+    pub wall_clock: Duration,
+    pub frame: CaptureFrame,
+}
+
+impl CaptureRecord {
+    /// Record for frame received right now, `None` for control frames
+    pub fn received(message: &Message) -> Option<Self> {
+        let frame = match message {
+            Message::Text(data) => CaptureFrame::Text(data.clone()),
+            Message::Binary(data) => CaptureFrame::Binary(data.clone()),
+            _ => return None,
+        };
+        Some(Self::now(frame))
+    }
+
+    /// Record for subscription acknowledged right now
+    pub fn subscription(subscription: CaptureSubscription) -> Self {
+        Self::now(CaptureFrame::Subscription(subscription))
+    }
+
+    fn now(frame: CaptureFrame) -> Self {
+        Self {
+            session: 0,
+            monotonic: monotonic_now(),
+            wall_clock: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default(),
+            frame,
+        }
+    }
+}
+
+pub struct CaptureWriter {
+    writer: Box<dyn Write + Send>,
+}
+
+impl std::fmt::Debug for CaptureWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("CaptureWriter").finish()
+    }
+}
+
+impl CaptureWriter {
+    /// Open capture for append and start a new session, header is written
+    /// only to the empty file
+    pub fn open(path: impl AsRef<Path>, zstd: bool) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+        let is_empty = file.metadata()?.len() == 0;
+        if !is_empty {
+            // reads start from the beginning of the file in append mode
+            let mut prefix = [0; ZSTD_MAGIC.len()];
+            (&file).read_exact(&mut prefix)?;
+            match (prefix == ZSTD_MAGIC, zstd) {
+                (true, false) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "capture is compressed with zstd, can not append plain session",
+                    ))
+                }
+                (false, true) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "capture is not compressed, can not append zstd session",
+                    ))
+                }
+                _ => {}
+            }
+        }
+
+        let file = BufWriter::new(file);
+        let mut writer: Box<dyn Write + Send> = if zstd {
+            Box::new(zstd::Encoder::new(file, 0)?.auto_finish())
+        } else {
+            Box::new(file)
+        };
+        if is_empty {
+            writer.write_all(MAGIC)?;
+        }
+
+        let mut writer = Self { writer };
+        writer.write_raw(
+            KIND_SESSION,
+            monotonic_now(),
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default(),
+            &[],
+        )?;
+        Ok(writer)
+    }
+
+    pub fn write(&mut self, record: &CaptureRecord) -> io::Result<()> {
+        let subscription;
+        let (kind, payload) = match &record.frame {
+            CaptureFrame::Text(data) => (KIND_TEXT, data.as_bytes()),
+            CaptureFrame::Binary(data) => (KIND_BINARY, data.as_slice()),
+            CaptureFrame::Subscription(value) => {
+                subscription = serde_json::to_vec(value)?;
+                (KIND_SUBSCRIPTION, subscription.as_slice())
+            }
+        };
+        self.write_raw(kind, record.monotonic, record.wall_clock, payload)
+    }
+
+    fn write_raw(
+        &mut self,
+        kind: u8,
+        monotonic: Duration,
+        wall_clock: Duration,
+        payload: &[u8],
+    ) -> io::Result<()> {
+        let length = u32::try_from(payload.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "frame is too large"))?;
+
+        self.writer.write_all(&[kind])?;
+        self.writer
+            .write_all(&(monotonic.as_nanos() as u64).to_le_bytes())?;
+        self.writer
+            .write_all(&(wall_clock.as_nanos() as u64).to_le_bytes())?;
+        self.writer.write_all(&length.to_le_bytes())?;
+        self.writer.write_all(payload)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+pub struct CaptureReader {
+    reader: Box<dyn Read + Send>,
+    /// Number of session start records read so far
+    sessions: u32,
+}
+
+impl std::fmt::Debug for CaptureReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("CaptureReader").finish()
+    }
+}
+
+impl CaptureReader {
+    /// Open capture, zstd compression is detected automatically
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+        let mut prefix = [0; ZSTD_MAGIC.len()];
+        file.read_exact(&mut prefix)?;
+
+        let reader = io::Cursor::new(prefix).chain(file);
+        let mut reader: Box<dyn Read + Send> = if prefix == ZSTD_MAGIC {
+            Box::new(zstd::Decoder::new(reader)?)
+        } else {
+            Box::new(reader)
+        };
+
+        let mut magic = [0; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid capture header",
+            ));
+        }
+
+        Ok(Self {
+            reader,
+            sessions: 0,
+        })
+    }
+
+    /// Read next record, `None` at the end of capture
+    pub fn read(&mut self) -> io::Result<Option<CaptureRecord>> {
+        loop {
+            let mut kind = [0; 1];
+            match self.reader.read_exact(&mut kind) {
+                Ok(()) => {}
+                Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(error) => return Err(error),
+            }
+            if kind[0] == KIND_SESSION {
+                self.read_payload()?;
+                self.sessions += 1;
+                continue;
+            }
+            return self.read_record(kind[0]).map(Some);
+        }
+    }
+
+    /// Timestamps and payload of the record after kind
+    fn read_payload(&mut self) -> io::Result<(Duration, Duration, Vec<u8>)> {
+        let mut buf = [0; 8];
+        self.reader.read_exact(&mut buf)?;
+        let monotonic = Duration::from_nanos(u64::from_le_bytes(buf));
+        self.reader.read_exact(&mut buf)?;
+        let wall_clock = Duration::from_nanos(u64::from_le_bytes(buf));
+        let mut buf = [0; 4];
+        self.reader.read_exact(&mut buf)?;
+        let mut payload = vec![0; u32::from_le_bytes(buf) as usize];
+        self.reader.read_exact(&mut payload)?;
+        Ok((monotonic, wall_clock, payload))
+    }
+
+    fn read_record(&mut self, kind: u8) -> io::Result<CaptureRecord> {
+        let (monotonic, wall_clock, payload) = self.read_payload()?;
+        let frame = match kind {
+            KIND_TEXT => CaptureFrame::Text(
+                String::from_utf8(payload)
+                    .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?,
+            ),
+            KIND_BINARY => CaptureFrame::Binary(payload),
+            KIND_SUBSCRIPTION => CaptureFrame::Subscription(serde_json::from_slice(&payload)?),
+            kind => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unknown frame kind: {kind}"),
+                ))
+            }
+        };
+
+        Ok(CaptureRecord {
+            // captures written before session records are one session
+            session: self.sessions.saturating_sub(1),
+            monotonic,
+            wall_clock,
+            frame,
+        })
+    }
+}
+
+impl Iterator for CaptureReader {
+    type Item = io::Result<CaptureRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
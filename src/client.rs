@@ -0,0 +1,1029 @@
+use {
+    crate::{
+        capture::{CaptureRecord, CaptureSubscription},
+        types::{
+            TransactionNotification, TransactionSubscribeConfig, TransactionSubscribeFilter,
+            TransactionSubscribeFilterDeprecated, VersionWhirligigInfo,
+        },
+    },
+    futures::{
+        channel::{mpsc, oneshot},
+        sink::SinkExt,
+        stream::{BoxStream, Stream, StreamExt},
+    },
+    jsonrpc_core::{Id as RpcId, Output as RpcOutput},
+    rand::Rng,
+    serde::{de::DeserializeOwned, Deserialize},
+    serde_json::{json, Value},
+    solana_account_decoder::UiAccount,
+    solana_rpc_client_api::{
+        config::{
+            RpcAccountInfoConfig, RpcBlockSubscribeConfig, RpcBlockSubscribeFilter,
+            RpcProgramAccountsConfig, RpcSignatureSubscribeConfig, RpcTransactionLogsConfig,
+            RpcTransactionLogsFilter,
+        },
+        response::{
+            Response as RpcResponse, RpcBlockUpdate, RpcKeyedAccount, RpcLogsResponse,
+            RpcSignatureResult, RpcVersionInfo, RpcVote, SlotInfo, SlotUpdate,
+        },
+    },
+    solana_sdk::{
+        clock::Slot, commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature,
+    },
+    std::{
+        collections::HashMap,
+        fmt,
+        pin::Pin,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        task::{Context, Poll},
+    },
+    tokio::{
+        net::TcpStream,
+        sync::broadcast,
+        time::{interval_at, sleep, Duration, Instant},
+    },
+    tokio_tungstenite::{
+        connect_async,
+        tungstenite::{self, protocol::Message},
+        MaybeTlsStream, WebSocketStream,
+    },
+    tracing::{info, warn},
+};
+
+pub type SubscriptionId = u64;
+
+pub type ClientResult<T> = Result<T, ClientError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("websocket error: {0}")]
+    WebSocket(Box<tungstenite::Error>),
+    #[error("failed to decode message: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid {method} payload: {error}")]
+    InvalidNotification {
+        method: String,
+        error: serde_json::Error,
+    },
+    #[error("request error: {0}")]
+    Rpc(jsonrpc_core::Error),
+    #[error("unexpected message: {0}")]
+    UnexpectedMessage(String),
+    #[error("connection closed")]
+    ConnectionClosed,
+    #[error("unsubscribe rejected by server")]
+    UnsubscribeRejected,
+}
+
+impl ClientError {
+    /// Error category, e.g. for metrics labels
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::WebSocket(_) => "websocket",
+            Self::Json(_) => "json",
+            Self::InvalidNotification { .. } => "invalid_notification",
+            Self::Rpc(_) => "rpc",
+            Self::UnexpectedMessage(_) => "unexpected_message",
+            Self::ConnectionClosed => "connection_closed",
+            Self::UnsubscribeRejected => "unsubscribe_rejected",
+        }
+    }
+}
+
+impl From<tungstenite::Error> for ClientError {
+    fn from(error: tungstenite::Error) -> Self {
+        Self::WebSocket(Box::new(error))
+    }
+}
+
+/// Message received from Whirligig, either response on request or notification
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RpcMessage {
+    Response(RpcOutput),
+    Notification(RpcNotification),
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcNotification {
+    method: String,
+    params: RpcNotificationParams,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcNotificationParams {
+    result: Value,
+    subscription: SubscriptionId,
+}
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Notification payload with size of the received message
+#[derive(Debug)]
+struct RawNotification {
+    result: Value,
+    size: usize,
+}
+
+type NotificationsSender = mpsc::UnboundedSender<ClientResult<RawNotification>>;
+
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    /// Send ping with interval, `None` disables pings
+    pub ping_interval: Option<Duration>,
+    /// Connection considered dead if nothing received within timeout
+    pub ping_timeout: Duration,
+    /// Reconnect on disconnect, `None` closes all subscriptions instead
+    pub reconnect: Option<ReconnectConfig>,
+    /// Send copy of every received data frame and subscription record for
+    /// every acknowledged subscribe request
+    pub capture: Option<mpsc::UnboundedSender<CaptureRecord>>,
+    /// Add payload size of every received data frame, cheaper than `capture`
+    /// when only throughput is needed
+    pub received_bytes: Option<Arc<AtomicU64>>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            ping_interval: None,
+            ping_timeout: Duration::from_secs(30),
+            reconnect: None,
+            capture: None,
+            received_bytes: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReconnectConfig {
+    /// Delay before the first reconnect attempt
+    pub min_backoff: Duration,
+    /// Upper bound for delay between reconnect attempts
+    pub max_backoff: Duration,
+    /// Give up after this number of failed attempts in a row
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        Self {
+            min_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(30),
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectConfig {
+    /// Exponential backoff with jitter: random delay in `[backoff / 2, backoff]`
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .min_backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_backoff);
+        backoff.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DisconnectReason {
+    Closed,
+    PingTimeout,
+    Error(String),
+}
+
+impl DisconnectReason {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Closed => "closed",
+            Self::PingTimeout => "ping_timeout",
+            Self::Error(_) => "error",
+        }
+    }
+}
+
+impl fmt::Display for DisconnectReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Closed => write!(f, "connection closed"),
+            Self::PingTimeout => write!(f, "ping timeout"),
+            Self::Error(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ConnectionEvent {
+    Disconnected {
+        reason: DisconnectReason,
+    },
+    Reconnected {
+        attempts: u32,
+        downtime: Duration,
+    },
+    /// Subscription was re-issued after reconnect, notifications after
+    /// `last_slot` could be missed
+    Gap {
+        subscription: SubscriptionId,
+        method: &'static str,
+        last_slot: Option<Slot>,
+    },
+}
+
+#[derive(Debug)]
+enum ClientRequest {
+    Request {
+        method: &'static str,
+        params: Value,
+        response_tx: oneshot::Sender<ClientResult<Value>>,
+    },
+    Subscribe {
+        method: &'static str,
+        params: Value,
+        notifications_tx: NotificationsSender,
+        response_tx: oneshot::Sender<ClientResult<SubscriptionId>>,
+    },
+    Unsubscribe {
+        id: SubscriptionId,
+        response_tx: Option<oneshot::Sender<ClientResult<()>>>,
+    },
+}
+
+#[derive(Debug)]
+enum PendingRequest {
+    Request {
+        response_tx: oneshot::Sender<ClientResult<Value>>,
+    },
+    Subscribe {
+        id: SubscriptionId,
+        response_tx: oneshot::Sender<ClientResult<SubscriptionId>>,
+    },
+    Resubscribe {
+        id: SubscriptionId,
+        method: &'static str,
+    },
+    Unsubscribe {
+        response_tx: Option<oneshot::Sender<ClientResult<()>>>,
+    },
+}
+
+#[derive(Debug)]
+struct ActiveSubscription {
+    method: &'static str,
+    params: Value,
+    notification: String,
+    notifications_tx: NotificationsSender,
+    server_id: Option<SubscriptionId>,
+    last_slot: Option<Slot>,
+}
+
+#[derive(Debug)]
+struct ConnectionState {
+    events_tx: broadcast::Sender<ConnectionEvent>,
+    next_request_id: u64,
+    next_subscription_id: SubscriptionId,
+    pending: HashMap<u64, PendingRequest>,
+    // subscriptions are keyed by client side id, which stays the same across reconnects
+    subscriptions: HashMap<SubscriptionId, ActiveSubscription>,
+    server_ids: HashMap<SubscriptionId, SubscriptionId>,
+    capture: Option<mpsc::UnboundedSender<CaptureRecord>>,
+}
+
+impl ConnectionState {
+    fn new(
+        events_tx: broadcast::Sender<ConnectionEvent>,
+        capture: Option<mpsc::UnboundedSender<CaptureRecord>>,
+    ) -> Self {
+        Self {
+            events_tx,
+            next_request_id: 0,
+            next_subscription_id: 0,
+            pending: HashMap::new(),
+            subscriptions: HashMap::new(),
+            server_ids: HashMap::new(),
+            capture,
+        }
+    }
+
+    fn create_message(&mut self, method: &str, params: &Value, request: PendingRequest) -> Message {
+        self.next_request_id += 1;
+        self.pending.insert(self.next_request_id, request);
+        Message::Text(
+            json!({
+                "jsonrpc": "2.0",
+                "id": self.next_request_id,
+                "method": method,
+                "params": params,
+            })
+            .to_string(),
+        )
+    }
+
+    fn handle_request(&mut self, request: ClientRequest) -> Option<Message> {
+        match request {
+            ClientRequest::Request {
+                method,
+                params,
+                response_tx,
+            } => {
+                Some(self.create_message(method, &params, PendingRequest::Request { response_tx }))
+            }
+            ClientRequest::Subscribe {
+                method,
+                params,
+                notifications_tx,
+                response_tx,
+            } => {
+                self.next_subscription_id += 1;
+                let id = self.next_subscription_id;
+                let message = self.create_message(
+                    method,
+                    &params,
+                    PendingRequest::Subscribe { id, response_tx },
+                );
+                self.subscriptions.insert(
+                    id,
+                    ActiveSubscription {
+                        method,
+                        params,
+                        notification: WhirligigClient::notification_method(method),
+                        notifications_tx,
+                        server_id: None,
+                        last_slot: None,
+                    },
+                );
+                Some(message)
+            }
+            ClientRequest::Unsubscribe { id, response_tx } => self.unsubscribe(id, response_tx),
+        }
+    }
+
+    /// Answer request locally while disconnected
+    fn handle_request_offline(&mut self, request: ClientRequest) {
+        match request {
+            ClientRequest::Request { response_tx, .. } => {
+                let _ = response_tx.send(Err(ClientError::ConnectionClosed));
+            }
+            ClientRequest::Subscribe { response_tx, .. } => {
+                let _ = response_tx.send(Err(ClientError::ConnectionClosed));
+            }
+            ClientRequest::Unsubscribe { id, response_tx } => {
+                // server side subscriptions are gone with the connection
+                self.subscriptions.remove(&id);
+                if let Some(response_tx) = response_tx {
+                    let _ = response_tx.send(Ok(()));
+                }
+            }
+        }
+    }
+
+    fn handle_message(&mut self, data: &str) -> Option<Message> {
+        match serde_json::from_str(data) {
+            Ok(RpcMessage::Response(output)) => self.handle_response(output),
+            Ok(RpcMessage::Notification(notification)) => {
+                let Some(id) = self
+                    .server_ids
+                    .get(&notification.params.subscription)
+                    .copied()
+                else {
+                    warn!("notification for unknown subscription: {data}");
+                    return None;
+                };
+                let subscription = self.subscriptions.get_mut(&id)?;
+                if subscription.notification != notification.method {
+                    warn!("unexpected notification for subscription {id}: {data}");
+                    return None;
+                }
+
+                let result = notification.params.result;
+                if let Some(slot) = Self::notification_slot(&result) {
+                    subscription.last_slot = Some(slot);
+                }
+                if subscription
+                    .notifications_tx
+                    .unbounded_send(Ok(RawNotification {
+                        result,
+                        size: data.len(),
+                    }))
+                    .is_err()
+                {
+                    return self.unsubscribe(id, None);
+                }
+                None
+            }
+            Err(error) => {
+                warn!("failed to decode message ({error}): {data}");
+                None
+            }
+        }
+    }
+
+    fn handle_response(&mut self, output: RpcOutput) -> Option<Message> {
+        let (id, result) = match output {
+            RpcOutput::Success(output) => (output.id, Ok(output.result)),
+            RpcOutput::Failure(failure) => (failure.id, Err(ClientError::Rpc(failure.error))),
+        };
+        let request = match id {
+            RpcId::Num(id) => self.pending.remove(&id),
+            _ => None,
+        };
+
+        match request {
+            Some(PendingRequest::Request { response_tx }) => {
+                let _ = response_tx.send(result);
+            }
+            Some(PendingRequest::Subscribe { id, response_tx }) => {
+                match result.and_then(|value| Ok(serde_json::from_value(value)?)) {
+                    Ok(server_id) => {
+                        self.set_server_id(id, server_id);
+                        let _ = response_tx.send(Ok(id));
+                    }
+                    Err(error) => {
+                        self.subscriptions.remove(&id);
+                        let _ = response_tx.send(Err(error));
+                    }
+                }
+            }
+            Some(PendingRequest::Resubscribe { id, method }) => {
+                match result.and_then(|value| Ok(serde_json::from_value(value)?)) {
+                    Ok(server_id) => {
+                        let Some(subscription) = self.subscriptions.get(&id) else {
+                            // dropped while subscribe request was in flight
+                            return Some(self.create_message(
+                                &WhirligigClient::unsubscribe_method(method),
+                                &json!([server_id]),
+                                PendingRequest::Unsubscribe { response_tx: None },
+                            ));
+                        };
+                        let _ = self.events_tx.send(ConnectionEvent::Gap {
+                            subscription: id,
+                            method: subscription.method,
+                            last_slot: subscription.last_slot,
+                        });
+                        self.set_server_id(id, server_id);
+                    }
+                    Err(error) => {
+                        if let Some(subscription) = self.subscriptions.remove(&id) {
+                            let _ = subscription.notifications_tx.unbounded_send(Err(error));
+                        }
+                    }
+                }
+            }
+            Some(PendingRequest::Unsubscribe { response_tx }) => {
+                let result = result
+                    .and_then(|value| Ok(serde_json::from_value::<bool>(value)?))
+                    .and_then(|ok| ok.then_some(()).ok_or(ClientError::UnsubscribeRejected));
+                match response_tx {
+                    Some(response_tx) => {
+                        let _ = response_tx.send(result);
+                    }
+                    None => {
+                        if let Err(error) = result {
+                            warn!("failed to unsubscribe: {error}");
+                        }
+                    }
+                }
+            }
+            None => warn!("response for unknown request: {id:?}"),
+        }
+        None
+    }
+
+    fn set_server_id(&mut self, id: SubscriptionId, server_id: SubscriptionId) {
+        if let Some(subscription) = self.subscriptions.get_mut(&id) {
+            subscription.server_id = Some(server_id);
+            self.server_ids.insert(server_id, id);
+            if let Some(capture) = &self.capture {
+                let _ = capture.unbounded_send(CaptureRecord::subscription(CaptureSubscription {
+                    id,
+                    method: subscription.method.to_owned(),
+                    params: subscription.params.clone(),
+                    subscription: server_id,
+                }));
+            }
+        }
+    }
+
+    fn unsubscribe(
+        &mut self,
+        id: SubscriptionId,
+        response_tx: Option<oneshot::Sender<ClientResult<()>>>,
+    ) -> Option<Message> {
+        if let Some(ActiveSubscription {
+            method,
+            server_id: Some(server_id),
+            ..
+        }) = self.subscriptions.remove(&id)
+        {
+            self.server_ids.remove(&server_id);
+            return Some(self.create_message(
+                &WhirligigClient::unsubscribe_method(method),
+                &json!([server_id]),
+                PendingRequest::Unsubscribe { response_tx },
+            ));
+        }
+
+        // not subscribed on the server right now, in-flight resubscribe
+        // would be cancelled once confirmed
+        if let Some(response_tx) = response_tx {
+            let _ = response_tx.send(Ok(()));
+        }
+        None
+    }
+
+    /// Slot from notification context, used to report gaps on reconnect
+    fn notification_slot(result: &Value) -> Option<Slot> {
+        result
+            .pointer("/context/slot")
+            .or_else(|| result.get("slot"))
+            // `rootNotification` is a bare slot
+            .unwrap_or(result)
+            .as_u64()
+    }
+
+    /// Fail in-flight requests and forget server ids, subscriptions itself
+    /// are kept to be replayed on the next connection
+    fn disconnect(&mut self) {
+        for request in std::mem::take(&mut self.pending).into_values() {
+            match request {
+                PendingRequest::Request { response_tx } => {
+                    let _ = response_tx.send(Err(ClientError::ConnectionClosed));
+                }
+                PendingRequest::Subscribe { id, response_tx } => {
+                    self.subscriptions.remove(&id);
+                    let _ = response_tx.send(Err(ClientError::ConnectionClosed));
+                }
+                PendingRequest::Resubscribe { .. } => {}
+                PendingRequest::Unsubscribe { response_tx } => {
+                    // subscriptions are dropped by server with connection
+                    if let Some(response_tx) = response_tx {
+                        let _ = response_tx.send(Ok(()));
+                    }
+                }
+            }
+        }
+        self.server_ids.clear();
+        for subscription in self.subscriptions.values_mut() {
+            subscription.server_id = None;
+        }
+    }
+
+    fn resubscribe(&mut self) -> Vec<Message> {
+        let subscriptions = self
+            .subscriptions
+            .iter()
+            .map(|(id, subscription)| (*id, subscription.method, subscription.params.clone()))
+            .collect::<Vec<_>>();
+        subscriptions
+            .into_iter()
+            .map(|(id, method, params)| {
+                self.create_message(method, &params, PendingRequest::Resubscribe { id, method })
+            })
+            .collect()
+    }
+
+    fn close(mut self) {
+        self.disconnect();
+        for subscription in self.subscriptions.into_values() {
+            let _ = subscription
+                .notifications_tx
+                .unbounded_send(Err(ClientError::ConnectionClosed));
+        }
+    }
+}
+
+/// Client for Whirligig WebSocket endpoint.
+///
+/// All requests and subscriptions are multiplexed over one WebSocket
+/// connection, the client can be cloned cheaply and shared between tasks.
+/// With [`ReconnectConfig`] connection is re-established on failures and all
+/// active subscriptions are re-issued with original params.
+#[derive(Debug, Clone)]
+pub struct WhirligigClient {
+    requests_tx: mpsc::UnboundedSender<ClientRequest>,
+    events_tx: broadcast::Sender<ConnectionEvent>,
+}
+
+impl WhirligigClient {
+    pub async fn connect(endpoint: &str) -> ClientResult<Self> {
+        Self::connect_with_config(endpoint, ClientConfig::default()).await
+    }
+
+    pub async fn connect_with_config(endpoint: &str, config: ClientConfig) -> ClientResult<Self> {
+        let (stream, _) = connect_async(endpoint).await?;
+        let (requests_tx, requests_rx) = mpsc::unbounded();
+        let (events_tx, _) = broadcast::channel(1024);
+        tokio::spawn(Self::run_connection(
+            endpoint.to_owned(),
+            config,
+            stream,
+            requests_rx,
+            events_tx.clone(),
+        ));
+        Ok(Self {
+            requests_tx,
+            events_tx,
+        })
+    }
+
+    /// Connection events: disconnects, reconnects and gaps in subscriptions
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ConnectionEvent> {
+        self.events_tx.subscribe()
+    }
+
+    async fn run_connection(
+        endpoint: String,
+        config: ClientConfig,
+        mut stream: WsStream,
+        mut requests_rx: mpsc::UnboundedReceiver<ClientRequest>,
+        events_tx: broadcast::Sender<ConnectionEvent>,
+    ) {
+        let mut state = ConnectionState::new(events_tx.clone(), config.capture.clone());
+        let mut messages = vec![];
+        while let Some(reason) =
+            Self::run_stream(&config, &mut stream, &mut requests_rx, &mut state, messages).await
+        {
+            warn!("disconnected: {reason}");
+            let _ = events_tx.send(ConnectionEvent::Disconnected { reason });
+            let Some(reconnect) = &config.reconnect else {
+                break;
+            };
+            state.disconnect();
+
+            let ts = Instant::now();
+            let mut attempts = 0;
+            let reconnected = 'reconnect: loop {
+                attempts += 1;
+                let attempt = async {
+                    sleep(reconnect.backoff(attempts)).await;
+                    connect_async(&endpoint).await
+                };
+                tokio::pin!(attempt);
+                // keep answering requests while disconnected, so unsubscribe
+                // does not hang and dropped client stops reconnecting
+                let result = loop {
+                    tokio::select! {
+                        request = requests_rx.next() => match request {
+                            Some(request) => state.handle_request_offline(request),
+                            None => break 'reconnect None,
+                        },
+                        result = &mut attempt => break result,
+                    }
+                };
+                match result {
+                    Ok((stream, _)) => break Some(stream),
+                    Err(error) => {
+                        warn!("failed to reconnect, attempt #{attempts}: {error}");
+                        if matches!(reconnect.max_attempts, Some(max) if attempts >= max) {
+                            break None;
+                        }
+                    }
+                }
+            };
+            let Some(reconnected) = reconnected else {
+                break;
+            };
+
+            stream = reconnected;
+            info!("reconnected after {attempts} attempts");
+            let _ = events_tx.send(ConnectionEvent::Reconnected {
+                attempts,
+                downtime: ts.elapsed(),
+            });
+            messages = state.resubscribe();
+        }
+        state.close();
+    }
+
+    /// Process requests and messages until disconnect, `None` returned if
+    /// client and all subscriptions were dropped
+    async fn run_stream(
+        config: &ClientConfig,
+        stream: &mut WsStream,
+        requests_rx: &mut mpsc::UnboundedReceiver<ClientRequest>,
+        state: &mut ConnectionState,
+        messages: Vec<Message>,
+    ) -> Option<DisconnectReason> {
+        for message in messages {
+            if let Err(error) = stream.send(message).await {
+                return Some(DisconnectReason::Error(error.to_string()));
+            }
+        }
+
+        let mut ping = interval_at(
+            Instant::now() + config.ping_interval.unwrap_or(config.ping_timeout),
+            config.ping_interval.unwrap_or(config.ping_timeout),
+        );
+        let mut last_received = Instant::now();
+        loop {
+            tokio::select! {
+                request = requests_rx.next() => {
+                    if let Some(message) = state.handle_request(request?) {
+                        if let Err(error) = stream.send(message).await {
+                            return Some(DisconnectReason::Error(error.to_string()));
+                        }
+                    }
+                }
+                message = stream.next() => {
+                    last_received = Instant::now();
+                    if let (
+                        Some(received_bytes),
+                        Some(Ok(message @ (Message::Text(_) | Message::Binary(_)))),
+                    ) = (&config.received_bytes, &message)
+                    {
+                        received_bytes.fetch_add(message.len() as u64, Ordering::Relaxed);
+                    }
+                    if let (Some(capture), Some(Ok(message))) = (&config.capture, &message) {
+                        if let Some(record) = CaptureRecord::received(message) {
+                            let _ = capture.unbounded_send(record);
+                        }
+                    }
+                    match message {
+                        Some(Ok(Message::Text(data))) => {
+                            if let Some(message) = state.handle_message(&data) {
+                                if let Err(error) = stream.send(message).await {
+                                    return Some(DisconnectReason::Error(error.to_string()));
+                                }
+                            }
+                        }
+                        Some(Ok(Message::Ping(_) | Message::Pong(_) | Message::Frame(_))) => {}
+                        Some(Ok(Message::Close(_))) | None => return Some(DisconnectReason::Closed),
+                        Some(Ok(message)) => {
+                            let error = format!("unexpected message: {message:?}");
+                            return Some(DisconnectReason::Error(error));
+                        }
+                        Some(Err(error)) => return Some(DisconnectReason::Error(error.to_string())),
+                    }
+                }
+                _ = ping.tick(), if config.ping_interval.is_some() => {
+                    if last_received.elapsed() > config.ping_timeout {
+                        return Some(DisconnectReason::PingTimeout);
+                    }
+                    if let Err(error) = stream.send(Message::Ping(vec![])).await {
+                        return Some(DisconnectReason::Error(error.to_string()));
+                    }
+                }
+            }
+        }
+    }
+
+    fn notification_method(method: &str) -> String {
+        format!("{}Notification", method.trim_end_matches("Subscribe"))
+    }
+
+    fn unsubscribe_method(method: &str) -> String {
+        format!("{}Unsubscribe", method.trim_end_matches("Subscribe"))
+    }
+
+    async fn send_request<T: DeserializeOwned>(
+        &self,
+        method: &'static str,
+        params: Value,
+    ) -> ClientResult<T> {
+        let (response_tx, response_rx) = oneshot::channel();
+        self.requests_tx
+            .unbounded_send(ClientRequest::Request {
+                method,
+                params,
+                response_tx,
+            })
+            .map_err(|_| ClientError::ConnectionClosed)?;
+        let value = response_rx
+            .await
+            .map_err(|_| ClientError::ConnectionClosed)??;
+        serde_json::from_value(value).map_err(Into::into)
+    }
+
+    async fn subscribe<T: DeserializeOwned + Send + 'static>(
+        &self,
+        method: &'static str,
+        params: Value,
+    ) -> ClientResult<Subscription<T>> {
+        let (notifications_tx, notifications_rx) = mpsc::unbounded();
+        let (response_tx, response_rx) = oneshot::channel();
+        self.requests_tx
+            .unbounded_send(ClientRequest::Subscribe {
+                method,
+                params,
+                notifications_tx,
+                response_tx,
+            })
+            .map_err(|_| ClientError::ConnectionClosed)?;
+        let id = response_rx
+            .await
+            .map_err(|_| ClientError::ConnectionClosed)??;
+
+        let notification = Self::notification_method(method);
+        let stats = Arc::new(SubscriptionStats::default());
+        let stream_stats = Arc::clone(&stats);
+        Ok(Subscription {
+            id,
+            requests_tx: Some(self.requests_tx.clone()),
+            stats,
+            stream: notifications_rx
+                .map(move |item| {
+                    item.and_then(|raw| {
+                        let ts = Instant::now();
+                        let result = serde_json::from_value(raw.result).map_err(|error| {
+                            ClientError::InvalidNotification {
+                                method: notification.clone(),
+                                error,
+                            }
+                        });
+                        stream_stats.record(raw.size, ts.elapsed());
+                        result
+                    })
+                })
+                .boxed(),
+        })
+    }
+
+    pub async fn get_version(&self) -> ClientResult<RpcVersionInfo> {
+        self.send_request("getVersion", json!([])).await
+    }
+
+    pub async fn get_version_whirligig(&self) -> ClientResult<VersionWhirligigInfo> {
+        self.send_request("getVersionWhirligig", json!([])).await
+    }
+
+    pub async fn account_subscribe(
+        &self,
+        pubkey: &Pubkey,
+        config: Option<RpcAccountInfoConfig>,
+    ) -> ClientResult<Subscription<RpcResponse<UiAccount>>> {
+        self.subscribe("accountSubscribe", json!([pubkey.to_string(), config]))
+            .await
+    }
+
+    pub async fn block_subscribe(
+        &self,
+        filter: RpcBlockSubscribeFilter,
+        config: Option<RpcBlockSubscribeConfig>,
+    ) -> ClientResult<Subscription<RpcResponse<RpcBlockUpdate>>> {
+        self.subscribe("blockSubscribe", json!([filter, config]))
+            .await
+    }
+
+    pub async fn logs_subscribe(
+        &self,
+        filter: RpcTransactionLogsFilter,
+        config: RpcTransactionLogsConfig,
+    ) -> ClientResult<Subscription<RpcResponse<RpcLogsResponse>>> {
+        self.subscribe("logsSubscribe", json!([filter, config]))
+            .await
+    }
+
+    pub async fn program_subscribe(
+        &self,
+        pubkey: &Pubkey,
+        config: Option<RpcProgramAccountsConfig>,
+    ) -> ClientResult<Subscription<RpcResponse<RpcKeyedAccount>>> {
+        self.subscribe("programSubscribe", json!([pubkey.to_string(), config]))
+            .await
+    }
+
+    pub async fn signature_subscribe(
+        &self,
+        signature: &Signature,
+        config: Option<RpcSignatureSubscribeConfig>,
+    ) -> ClientResult<Subscription<RpcResponse<RpcSignatureResult>>> {
+        self.subscribe("signatureSubscribe", json!([signature.to_string(), config]))
+            .await
+    }
+
+    pub async fn slot_subscribe(&self) -> ClientResult<Subscription<SlotInfo>> {
+        self.subscribe("slotSubscribe", json!([])).await
+    }
+
+    pub async fn slots_updates_subscribe(&self) -> ClientResult<Subscription<SlotUpdate>> {
+        self.subscribe("slotsUpdatesSubscribe", json!([])).await
+    }
+
+    pub async fn root_subscribe(&self) -> ClientResult<Subscription<Slot>> {
+        self.subscribe("rootSubscribe", json!([])).await
+    }
+
+    pub async fn vote_subscribe(&self) -> ClientResult<Subscription<RpcVote>> {
+        self.subscribe("voteSubscribe", json!([])).await
+    }
+
+    pub async fn transaction_subscribe(
+        &self,
+        filter: TransactionSubscribeFilter,
+        config: TransactionSubscribeConfig,
+    ) -> ClientResult<Subscription<TransactionNotification>> {
+        self.subscribe("transactionSubscribe", json!([filter, config]))
+            .await
+    }
+
+    /// Subscribe with filter in the deprecated format, notifications have the
+    /// same payload as in [`Self::transaction_subscribe`]
+    pub async fn transaction_subscribe_deprecated(
+        &self,
+        filter: TransactionSubscribeFilterDeprecated,
+        commitment: CommitmentConfig,
+    ) -> ClientResult<Subscription<TransactionNotification>> {
+        self.subscribe("transactionSubscribe", json!([filter, commitment]))
+            .await
+    }
+}
+
+/// Counters of received notifications, shared between subscription stream
+/// and observers, see [`Subscription::stats`]
+#[derive(Debug, Default)]
+pub struct SubscriptionStats {
+    bytes: AtomicU64,
+    decode_nanos: AtomicU64,
+}
+
+impl SubscriptionStats {
+    fn record(&self, size: usize, decode_time: Duration) {
+        self.bytes.fetch_add(size as u64, Ordering::Relaxed);
+        self.decode_nanos.fetch_add(
+            u64::try_from(decode_time.as_nanos()).unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+    }
+
+    /// Size of received notification messages, counted when notification is decoded
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Total time spent on decoding of notifications
+    pub fn decode_time(&self) -> Duration {
+        Duration::from_nanos(self.decode_nanos.load(Ordering::Relaxed))
+    }
+}
+
+/// Stream of notifications for one subscription.
+///
+/// Dropping the stream sends unsubscribe request without waiting for the
+/// server confirmation, use [`Subscription::unsubscribe`] to await it.
+pub struct Subscription<T> {
+    id: SubscriptionId,
+    // connection is closed once client and all subscriptions are dropped
+    requests_tx: Option<mpsc::UnboundedSender<ClientRequest>>,
+    stats: Arc<SubscriptionStats>,
+    stream: BoxStream<'static, ClientResult<T>>,
+}
+
+impl<T> fmt::Debug for Subscription<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Subscription")
+            .field("id", &self.id)
+            .finish()
+    }
+}
+
+impl<T> Subscription<T> {
+    /// Client side id, stays the same when subscription re-issued on reconnect
+    pub const fn id(&self) -> SubscriptionId {
+        self.id
+    }
+
+    /// Counters of received notifications, updated as the stream is polled
+    pub fn stats(&self) -> Arc<SubscriptionStats> {
+        Arc::clone(&self.stats)
+    }
+
+    /// Send unsubscribe request and wait for the server confirmation
+    pub async fn unsubscribe(mut self) -> ClientResult<()> {
+        let Some(requests_tx) = self.requests_tx.take() else {
+            return Ok(());
+        };
+        let (response_tx, response_rx) = oneshot::channel();
+        requests_tx
+            .unbounded_send(ClientRequest::Unsubscribe {
+                id: self.id,
+                response_tx: Some(response_tx),
+            })
+            .map_err(|_| ClientError::ConnectionClosed)?;
+        response_rx
+            .await
+            .map_err(|_| ClientError::ConnectionClosed)?
+    }
+}
+
+impl<T> Drop for Subscription<T> {
+    fn drop(&mut self) {
+        if let Some(requests_tx) = self.requests_tx.take() {
+            let _ = requests_tx.unbounded_send(ClientRequest::Unsubscribe {
+                id: self.id,
+                response_tx: None,
+            });
+        }
+    }
+}
+
+impl<T> Stream for Subscription<T> {
+    type Item = ClientResult<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.stream.poll_next_unpin(cx)
+    }
+}
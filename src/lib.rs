@@ -1,3 +1,9 @@
+pub mod capture;
+pub mod client;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod types;
+
 use tracing_subscriber::{
     filter::{EnvFilter, LevelFilter},
     layer::SubscriberExt,
@@ -6,7 +12,9 @@ use tracing_subscriber::{
 
 pub fn tracer_init() -> anyhow::Result<()> {
     let is_atty = atty::is(atty::Stream::Stdout) && atty::is(atty::Stream::Stderr);
-    let io_layer = tracing_subscriber::fmt::layer().with_ansi(is_atty);
+    let io_layer = tracing_subscriber::fmt::layer()
+        .with_ansi(is_atty)
+        .with_writer(std::io::stderr);
 
     let env_layer = EnvFilter::builder()
         .with_default_directive(LevelFilter::INFO.into())
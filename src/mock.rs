@@ -0,0 +1,344 @@
+//! In-process mock of Whirligig WebSocket server for tests.
+//!
+//! Server accepts any `*Subscribe` / `*Unsubscribe` request, answers on
+//! `getVersion` and `getVersionWhirligig`, notifications are pushed from the
+//! test with [`MockServer::notify`] or scripted per subscribe method with
+//! [`MockServer::script`].
+//!
+//! Available with `mock` feature.
+
+use {
+    crate::{
+        client::SubscriptionId,
+        types::{VersionWhirligigInfo, VersionWhirligigInfoExtra, VersionWhirligigInfoVersion},
+    },
+    futures::{channel::mpsc, sink::SinkExt, stream::StreamExt},
+    serde::Deserialize,
+    serde_json::{json, Value},
+    solana_rpc_client_api::response::RpcVersionInfo,
+    std::{
+        collections::{HashMap, VecDeque},
+        io,
+        net::SocketAddr,
+        sync::{Arc, Mutex, MutexGuard},
+    },
+    tokio::{
+        net::{TcpListener, TcpStream},
+        task::JoinHandle,
+    },
+    tokio_tungstenite::{accept_async, tungstenite::protocol::Message},
+    tracing::warn,
+};
+
+#[derive(Debug, Deserialize)]
+struct MockRequest {
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// Subscription created by the client on the mock server
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockSubscription {
+    /// Server side id, sent in notifications
+    pub id: SubscriptionId,
+    pub method: String,
+    pub params: Value,
+}
+
+#[derive(Debug)]
+struct MockState {
+    version: RpcVersionInfo,
+    version_whirligig: VersionWhirligigInfo,
+    scripts: HashMap<String, Vec<Value>>,
+    errors: HashMap<String, VecDeque<jsonrpc_core::Error>>,
+    next_connection_id: u64,
+    connections: HashMap<u64, mpsc::UnboundedSender<Message>>,
+    next_subscription_id: SubscriptionId,
+    subscriptions: HashMap<SubscriptionId, (u64, MockSubscription)>,
+}
+
+impl Default for MockState {
+    fn default() -> Self {
+        Self {
+            version: RpcVersionInfo {
+                solana_core: "1.17.0".to_owned(),
+                feature_set: Some(0),
+            },
+            version_whirligig: VersionWhirligigInfo {
+                version: VersionWhirligigInfoVersion {
+                    package: "yellowstone-whirligig".to_owned(),
+                    version: "0.0.0".to_owned(),
+                    proto: "0.0.0".to_owned(),
+                    solana: "1.17.0".to_owned(),
+                    git: "mock".to_owned(),
+                    rustc: "mock".to_owned(),
+                    buildts: "mock".to_owned(),
+                },
+                extra: VersionWhirligigInfoExtra {
+                    hostname: "mock".to_owned(),
+                },
+            },
+            scripts: HashMap::new(),
+            errors: HashMap::new(),
+            next_connection_id: 0,
+            connections: HashMap::new(),
+            next_subscription_id: 0,
+            subscriptions: HashMap::new(),
+        }
+    }
+}
+
+impl MockState {
+    fn handle_request(
+        &mut self,
+        connection_id: u64,
+        request: MockRequest,
+    ) -> (Value, Vec<Message>) {
+        let mut notifications = vec![];
+        let result = if let Some(error) = self
+            .errors
+            .get_mut(&request.method)
+            .and_then(VecDeque::pop_front)
+        {
+            Err(error)
+        } else if request.method == "getVersion" {
+            Ok(json!(self.version))
+        } else if request.method == "getVersionWhirligig" {
+            Ok(json!(self.version_whirligig))
+        } else if request.method.ends_with("Unsubscribe") {
+            let id = request.params.get(0).and_then(Value::as_u64);
+            let removed = match id.and_then(|id| self.subscriptions.get(&id)) {
+                Some((connection, _)) if *connection == connection_id => {
+                    self.subscriptions.remove(&id.unwrap_or_default());
+                    true
+                }
+                _ => false,
+            };
+            Ok(json!(removed))
+        } else if let Some(kind) = request.method.strip_suffix("Subscribe") {
+            self.next_subscription_id += 1;
+            let id = self.next_subscription_id;
+            let notification = format!("{kind}Notification");
+            for result in self.scripts.get(&request.method).into_iter().flatten() {
+                notifications.push(Self::notification(&notification, id, result));
+            }
+            self.subscriptions.insert(
+                id,
+                (
+                    connection_id,
+                    MockSubscription {
+                        id,
+                        method: request.method,
+                        params: request.params,
+                    },
+                ),
+            );
+            Ok(json!(id))
+        } else {
+            Err(jsonrpc_core::Error::method_not_found())
+        };
+
+        let response = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "result": result, "id": request.id }),
+            Err(error) => json!({ "jsonrpc": "2.0", "error": error, "id": request.id }),
+        };
+        (response, notifications)
+    }
+
+    fn notification(method: &str, id: SubscriptionId, result: &Value) -> Message {
+        let message = json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": {
+                "result": result,
+                "subscription": id,
+            }
+        });
+        Message::Text(message.to_string())
+    }
+}
+
+/// Mock server listening on random local port, stopped on drop
+#[derive(Debug)]
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<MockState>>,
+    accept: JoinHandle<()>,
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.accept.abort();
+        self.disconnect();
+    }
+}
+
+impl MockServer {
+    pub async fn start() -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let accept = tokio::spawn(Self::run_accept(listener, Arc::clone(&state)));
+        Ok(Self {
+            addr,
+            state,
+            accept,
+        })
+    }
+
+    async fn run_accept(listener: TcpListener, state: Arc<Mutex<MockState>>) {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(Self::run_connection(stream, Arc::clone(&state)));
+                }
+                Err(error) => warn!("mock server: failed to accept connection: {error}"),
+            }
+        }
+    }
+
+    async fn run_connection(stream: TcpStream, state: Arc<Mutex<MockState>>) {
+        let mut stream = match accept_async(stream).await {
+            Ok(stream) => stream,
+            Err(error) => {
+                warn!("mock server: failed to accept WebSocket: {error}");
+                return;
+            }
+        };
+
+        let (messages_tx, mut messages_rx) = mpsc::unbounded();
+        let connection_id = {
+            let mut state = Self::lock(&state);
+            state.next_connection_id += 1;
+            let connection_id = state.next_connection_id;
+            state.connections.insert(connection_id, messages_tx.clone());
+            connection_id
+        };
+
+        loop {
+            tokio::select! {
+                message = stream.next() => {
+                    let data = match message {
+                        Some(Ok(Message::Text(data))) => data,
+                        Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                        Some(Ok(_)) => continue,
+                    };
+                    let request = match serde_json::from_str::<MockRequest>(&data) {
+                        Ok(request) => request,
+                        Err(error) => {
+                            warn!("mock server: invalid request ({error}): {data}");
+                            continue;
+                        }
+                    };
+
+                    let (response, notifications) =
+                        Self::lock(&state).handle_request(connection_id, request);
+                    if stream.send(Message::Text(response.to_string())).await.is_err() {
+                        break;
+                    }
+                    for message in notifications {
+                        let _ = messages_tx.unbounded_send(message);
+                    }
+                }
+                Some(message) = messages_rx.next() => {
+                    let is_close = matches!(message, Message::Close(_));
+                    if stream.send(message).await.is_err() || is_close {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let mut state = Self::lock(&state);
+        state.connections.remove(&connection_id);
+        state
+            .subscriptions
+            .retain(|_id, (connection, _)| *connection != connection_id);
+    }
+
+    fn lock(state: &Mutex<MockState>) -> MutexGuard<'_, MockState> {
+        state.lock().unwrap_or_else(|error| error.into_inner())
+    }
+
+    /// WebSocket endpoint for the client
+    pub fn endpoint(&self) -> String {
+        format!("ws://{}/", self.addr)
+    }
+
+    pub fn set_version(&self, version: RpcVersionInfo) {
+        Self::lock(&self.state).version = version;
+    }
+
+    pub fn set_version_whirligig(&self, version: VersionWhirligigInfo) {
+        Self::lock(&self.state).version_whirligig = version;
+    }
+
+    /// Notifications sent right after every new subscription with `method`,
+    /// e.g. `slotSubscribe`
+    pub fn script(&self, method: impl Into<String>, notifications: Vec<Value>) {
+        Self::lock(&self.state)
+            .scripts
+            .insert(method.into(), notifications);
+    }
+
+    /// Fail next request with `method`, errors are queued
+    pub fn fail_next(&self, method: impl Into<String>, error: jsonrpc_core::Error) {
+        Self::lock(&self.state)
+            .errors
+            .entry(method.into())
+            .or_default()
+            .push_back(error);
+    }
+
+    /// Send notification to all subscriptions with `method`, e.g.
+    /// `slotSubscribe`, returns number of notified subscriptions
+    pub fn notify(&self, method: &str, result: Value) -> usize {
+        let state = Self::lock(&self.state);
+        let notification = format!("{}Notification", method.trim_end_matches("Subscribe"));
+        let mut notified = 0;
+        for (connection, subscription) in state.subscriptions.values() {
+            if subscription.method == method {
+                if let Some(messages_tx) = state.connections.get(connection) {
+                    let message = MockState::notification(&notification, subscription.id, &result);
+                    if messages_tx.unbounded_send(message).is_ok() {
+                        notified += 1;
+                    }
+                }
+            }
+        }
+        notified
+    }
+
+    /// Send raw text frame to all connections
+    pub fn send_raw(&self, data: impl Into<String>) {
+        let data = data.into();
+        for messages_tx in Self::lock(&self.state).connections.values() {
+            let _ = messages_tx.unbounded_send(Message::Text(data.clone()));
+        }
+    }
+
+    /// Close all connections, subscriptions are removed
+    pub fn disconnect(&self) {
+        for messages_tx in Self::lock(&self.state).connections.values() {
+            let _ = messages_tx.unbounded_send(Message::Close(None));
+        }
+    }
+
+    /// Number of open connections
+    pub fn connections(&self) -> usize {
+        Self::lock(&self.state).connections.len()
+    }
+
+    /// Active subscriptions over all connections ordered by id
+    pub fn subscriptions(&self) -> Vec<MockSubscription> {
+        let mut subscriptions = Self::lock(&self.state)
+            .subscriptions
+            .values()
+            .map(|(_connection, subscription)| subscription.clone())
+            .collect::<Vec<_>>();
+        subscriptions.sort_by_key(|subscription| subscription.id);
+        subscriptions
+    }
+}
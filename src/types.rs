@@ -0,0 +1,79 @@
+use {
+    serde::{Deserialize, Serialize},
+    solana_sdk::{clock::Slot, commitment_config::CommitmentConfig},
+    solana_transaction_status::{
+        EncodedTransactionWithStatusMeta, TransactionDetails, UiTransactionEncoding,
+    },
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionWhirligigInfoVersion {
+    pub package: String,
+    pub version: String,
+    pub proto: String,
+    pub solana: String,
+    pub git: String,
+    pub rustc: String,
+    pub buildts: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionWhirligigInfoExtra {
+    #[serde(default)]
+    pub hostname: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionWhirligigInfo {
+    pub version: VersionWhirligigInfoVersion,
+    pub extra: VersionWhirligigInfoExtra,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct TransactionSubscribeFilterAccounts {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub required: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct TransactionSubscribeFilter {
+    pub vote: Option<bool>,
+    pub failed: Option<bool>,
+    pub signature: Option<String>,
+    pub accounts: TransactionSubscribeFilterAccounts,
+}
+
+/// Filter in the format used by Whirligig before `accounts` were grouped
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct TransactionSubscribeFilterDeprecated {
+    pub vote: Option<bool>,
+    pub failed: Option<bool>,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub required: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionSubscribeConfig {
+    #[serde(flatten)]
+    pub commitment: Option<CommitmentConfig>,
+    pub encoding: Option<UiTransactionEncoding>,
+    pub transaction_details: Option<TransactionDetails>,
+    pub show_rewards: Option<bool>,
+    pub max_supported_transaction_version: Option<u8>,
+}
+
+/// Payload of `transactionNotification`
+///
+/// Shape of `transaction` depends on requested `encoding`, it's missing for
+/// `transactionDetails` set to `signatures` or `none`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionNotification {
+    pub signature: String,
+    pub slot: Slot,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction: Option<EncodedTransactionWithStatusMeta>,
+}
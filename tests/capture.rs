@@ -0,0 +1,110 @@
+use {
+    serde_json::json,
+    std::{
+        fs, io,
+        path::{Path, PathBuf},
+        time::Duration,
+    },
+    whirligig_client::capture::{
+        CaptureFrame, CaptureReader, CaptureRecord, CaptureSubscription, CaptureWriter,
+    },
+};
+
+fn capture_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "whirligig-client-{}-{name}.cap",
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+fn records() -> Vec<CaptureRecord> {
+    vec![
+        CaptureRecord {
+            session: 0,
+            monotonic: Duration::from_millis(1),
+            wall_clock: Duration::from_secs(1_700_000_000),
+            frame: CaptureFrame::Text(r#"{"jsonrpc":"2.0","result":1,"id":1}"#.to_owned()),
+        },
+        CaptureRecord {
+            session: 0,
+            monotonic: Duration::from_millis(2),
+            wall_clock: Duration::from_secs(1_700_000_001),
+            frame: CaptureFrame::Binary(vec![0, 1, 2, 3]),
+        },
+        CaptureRecord {
+            session: 0,
+            monotonic: Duration::from_millis(3),
+            wall_clock: Duration::from_secs(1_700_000_002),
+            frame: CaptureFrame::Subscription(CaptureSubscription {
+                id: 1,
+                method: "slotSubscribe".to_owned(),
+                params: json!([]),
+                subscription: 42,
+            }),
+        },
+    ]
+}
+
+fn write(path: &Path, zstd: bool, records: &[CaptureRecord]) -> io::Result<()> {
+    let mut writer = CaptureWriter::open(path, zstd)?;
+    for record in records {
+        writer.write(record)?;
+    }
+    writer.flush()
+}
+
+fn read(path: &Path) -> Vec<CaptureRecord> {
+    CaptureReader::open(path)
+        .unwrap()
+        .collect::<io::Result<Vec<_>>>()
+        .unwrap()
+}
+
+#[test]
+fn round_trip_plain() {
+    let path = capture_path("plain");
+    write(&path, false, &records()).unwrap();
+    assert_eq!(read(&path), records());
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn round_trip_zstd() {
+    let path = capture_path("zstd");
+    write(&path, true, &records()).unwrap();
+    assert_eq!(fs::read(&path).unwrap()[..4], [0x28, 0xb5, 0x2f, 0xfd]);
+    assert_eq!(read(&path), records());
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn append_sessions() {
+    for zstd in [false, true] {
+        let path = capture_path(&format!("append-{zstd}"));
+        write(&path, zstd, &records()).unwrap();
+        write(&path, zstd, &records()[..1]).unwrap();
+
+        let mut expected = records();
+        expected.push(CaptureRecord {
+            session: 1,
+            ..records()[0].clone()
+        });
+        assert_eq!(read(&path), expected);
+        fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn append_compression_mismatch() {
+    for zstd in [false, true] {
+        let path = capture_path(&format!("mismatch-{zstd}"));
+        write(&path, zstd, &records()).unwrap();
+
+        let error = CaptureWriter::open(&path, !zstd).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(read(&path), records());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,587 @@
+use {
+    futures::StreamExt,
+    serde_json::{json, Value},
+    solana_rpc_client_api::response::{SlotTransactionStats, SlotUpdate},
+    solana_sdk::commitment_config::CommitmentConfig,
+    solana_transaction_status::{
+        EncodedTransaction, TransactionBinaryEncoding, TransactionDetails, UiInstruction,
+        UiMessage, UiParsedInstruction, UiTransactionEncoding,
+    },
+    std::{
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        time::Duration,
+    },
+    tokio::time::timeout,
+    whirligig_client::{
+        client::{
+            ClientConfig, ClientError, ClientResult, ConnectionEvent, ReconnectConfig,
+            WhirligigClient,
+        },
+        mock::MockServer,
+        types::{
+            TransactionNotification, TransactionSubscribeConfig, TransactionSubscribeFilter,
+            TransactionSubscribeFilterDeprecated,
+        },
+    },
+};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[tokio::test]
+async fn get_version() {
+    let server = MockServer::start().await.unwrap();
+    let client = WhirligigClient::connect(&server.endpoint()).await.unwrap();
+
+    let version = client.get_version().await.unwrap();
+    assert_eq!(version.solana_core, "1.17.0");
+
+    let version = client.get_version_whirligig().await.unwrap();
+    assert_eq!(version.extra.hostname, "mock");
+}
+
+#[tokio::test]
+async fn slot_subscribe() {
+    let server = MockServer::start().await.unwrap();
+    server.script(
+        "slotSubscribe",
+        vec![json!({ "slot": 1, "parent": 0, "root": 0 })],
+    );
+    let client = WhirligigClient::connect(&server.endpoint()).await.unwrap();
+
+    let mut stream = client.slot_subscribe().await.unwrap();
+    let slot = timeout(TIMEOUT, stream.next()).await.unwrap().unwrap();
+    assert_eq!(slot.unwrap().slot, 1);
+
+    assert_eq!(
+        server.notify(
+            "slotSubscribe",
+            json!({ "slot": 2, "parent": 1, "root": 0 })
+        ),
+        1
+    );
+    let slot = timeout(TIMEOUT, stream.next()).await.unwrap().unwrap();
+    assert_eq!(slot.unwrap().slot, 2);
+
+    assert_eq!(server.subscriptions().len(), 1);
+    stream.unsubscribe().await.unwrap();
+    assert!(server.subscriptions().is_empty());
+}
+
+#[tokio::test]
+async fn subscription_stats() {
+    let server = MockServer::start().await.unwrap();
+    let client = WhirligigClient::connect(&server.endpoint()).await.unwrap();
+
+    let mut stream = client.slot_subscribe().await.unwrap();
+    let stats = stream.stats();
+    assert_eq!(stats.bytes(), 0);
+
+    server.notify(
+        "slotSubscribe",
+        json!({ "slot": 1, "parent": 0, "root": 0 }),
+    );
+    timeout(TIMEOUT, stream.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let bytes = stats.bytes();
+    assert!(bytes > 0);
+
+    server.notify("slotSubscribe", json!({ "slot": "invalid" }));
+    let error = timeout(TIMEOUT, stream.next()).await.unwrap().unwrap();
+    assert!(error.is_err());
+    assert!(stats.bytes() > bytes);
+}
+
+#[tokio::test]
+async fn slots_updates_subscribe() {
+    let server = MockServer::start().await.unwrap();
+    server.script(
+        "slotsUpdatesSubscribe",
+        vec![
+            json!({ "type": "firstShredReceived", "slot": 1, "timestamp": 0 }),
+            json!({ "type": "completed", "slot": 1, "timestamp": 0 }),
+            json!({ "type": "createdBank", "slot": 1, "parent": 0, "timestamp": 0 }),
+            json!({
+                "type": "frozen",
+                "slot": 1,
+                "timestamp": 0,
+                "stats": {
+                    "numTransactionEntries": 1,
+                    "numSuccessfulTransactions": 1,
+                    "numFailedTransactions": 0,
+                    "maxTransactionsPerEntry": 1,
+                },
+            }),
+            json!({ "type": "dead", "slot": 2, "timestamp": 0, "err": "dead" }),
+            json!({ "type": "optimisticConfirmation", "slot": 1, "timestamp": 0 }),
+            json!({ "type": "root", "slot": 1, "timestamp": 0 }),
+        ],
+    );
+    let client = WhirligigClient::connect(&server.endpoint()).await.unwrap();
+
+    let stream = client.slots_updates_subscribe().await.unwrap();
+    let updates = timeout(TIMEOUT, stream.take(7).collect::<Vec<_>>())
+        .await
+        .unwrap();
+    assert!(matches!(
+        updates[3],
+        Ok(SlotUpdate::Frozen {
+            slot: 1,
+            stats: SlotTransactionStats {
+                num_successful_transactions: 1,
+                ..
+            },
+            ..
+        })
+    ));
+    assert!(matches!(updates[6], Ok(SlotUpdate::Root { slot: 1, .. })));
+}
+
+#[tokio::test]
+async fn root_subscribe() {
+    let server = MockServer::start().await.unwrap();
+    server.script("rootSubscribe", vec![json!(1), json!(2)]);
+    let client = WhirligigClient::connect(&server.endpoint()).await.unwrap();
+
+    let stream = client.root_subscribe().await.unwrap();
+    let roots = timeout(TIMEOUT, stream.take(2).collect::<Vec<_>>())
+        .await
+        .unwrap();
+    assert_eq!(
+        roots.into_iter().collect::<ClientResult<Vec<_>>>().unwrap(),
+        [1, 2]
+    );
+}
+
+#[tokio::test]
+async fn vote_subscribe() {
+    let server = MockServer::start().await.unwrap();
+    server.script(
+        "voteSubscribe",
+        vec![json!({
+            "votePubkey": "Vote111111111111111111111111111111111111111",
+            "slots": [1, 2],
+            "hash": "EkSnNWid2cvwEVnVx9aBqawnmiCNiDgp3gUdkDPTKN1N",
+            "timestamp": 1_700_000_000,
+            "signature": SIGNATURE,
+        })],
+    );
+    let client = WhirligigClient::connect(&server.endpoint()).await.unwrap();
+
+    let mut stream = client.vote_subscribe().await.unwrap();
+    let vote = timeout(TIMEOUT, stream.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        vote.vote_pubkey,
+        "Vote111111111111111111111111111111111111111"
+    );
+    assert_eq!(vote.slots, [1, 2]);
+    assert_eq!(vote.timestamp, Some(1_700_000_000));
+
+    server.notify("voteSubscribe", json!({ "slots": "invalid" }));
+    let error = timeout(TIMEOUT, stream.next()).await.unwrap().unwrap();
+    assert!(
+        matches!(error, Err(ClientError::InvalidNotification { .. })),
+        "{error:?}"
+    );
+}
+
+#[tokio::test]
+async fn request_error() {
+    let server = MockServer::start().await.unwrap();
+    server.fail_next("slotSubscribe", jsonrpc_core::Error::internal_error());
+    let client = WhirligigClient::connect(&server.endpoint()).await.unwrap();
+
+    let error = client.slot_subscribe().await.unwrap_err();
+    assert!(matches!(error, ClientError::Rpc(_)), "{error:?}");
+    assert_eq!(error.as_str(), "rpc");
+    assert!(client.slot_subscribe().await.is_ok());
+}
+
+#[tokio::test]
+async fn invalid_notification() {
+    let server = MockServer::start().await.unwrap();
+    let client = WhirligigClient::connect(&server.endpoint()).await.unwrap();
+
+    let mut stream = client.slot_subscribe().await.unwrap();
+    server.notify("slotSubscribe", json!({ "slot": "invalid" }));
+    let error = timeout(TIMEOUT, stream.next()).await.unwrap().unwrap();
+    assert!(
+        matches!(error, Err(ClientError::InvalidNotification { .. })),
+        "{error:?}"
+    );
+}
+
+#[tokio::test]
+async fn reconnect() {
+    let server = MockServer::start().await.unwrap();
+    let client = WhirligigClient::connect_with_config(
+        &server.endpoint(),
+        ClientConfig {
+            reconnect: Some(ReconnectConfig {
+                min_backoff: Duration::from_millis(10),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    let mut events = client.subscribe_events();
+
+    let mut stream = client.slot_subscribe().await.unwrap();
+    server.disconnect();
+    loop {
+        let event = timeout(TIMEOUT, events.recv()).await.unwrap().unwrap();
+        if matches!(event, ConnectionEvent::Gap { .. }) {
+            break;
+        }
+    }
+
+    timeout(TIMEOUT, async {
+        while server.notify(
+            "slotSubscribe",
+            json!({ "slot": 3, "parent": 2, "root": 0 }),
+        ) == 0
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    let slot = timeout(TIMEOUT, stream.next()).await.unwrap().unwrap();
+    assert_eq!(slot.unwrap().slot, 3);
+}
+
+#[tokio::test]
+async fn unsubscribe_while_disconnected() {
+    let server = MockServer::start().await.unwrap();
+    let client = WhirligigClient::connect_with_config(
+        &server.endpoint(),
+        ClientConfig {
+            reconnect: Some(ReconnectConfig {
+                min_backoff: Duration::from_secs(3600),
+                max_backoff: Duration::from_secs(3600),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    let mut events = client.subscribe_events();
+
+    let stream = client.slot_subscribe().await.unwrap();
+    server.disconnect();
+    let event = timeout(TIMEOUT, events.recv()).await.unwrap().unwrap();
+    assert!(matches!(event, ConnectionEvent::Disconnected { .. }));
+
+    timeout(TIMEOUT, stream.unsubscribe())
+        .await
+        .unwrap()
+        .unwrap();
+    let error = timeout(TIMEOUT, client.get_version())
+        .await
+        .unwrap()
+        .unwrap_err();
+    assert!(matches!(error, ClientError::ConnectionClosed), "{error:?}");
+}
+
+#[tokio::test]
+async fn received_bytes() {
+    let server = MockServer::start().await.unwrap();
+    let received_bytes = Arc::new(AtomicU64::new(0));
+    let client = WhirligigClient::connect_with_config(
+        &server.endpoint(),
+        ClientConfig {
+            received_bytes: Some(Arc::clone(&received_bytes)),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    let mut stream = client.slot_subscribe().await.unwrap();
+    let subscribed = received_bytes.load(Ordering::Relaxed);
+    assert!(subscribed > 0);
+
+    server.notify(
+        "slotSubscribe",
+        json!({ "slot": 1, "parent": 0, "root": 0 }),
+    );
+    timeout(TIMEOUT, stream.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(received_bytes.load(Ordering::Relaxed) > subscribed);
+}
+
+const SIGNATURE: &str =
+    "5h6xBEauJ3PK6SWCZ1PGjBvj8vDdWG3KpwATGy1ARAXFSDwt8GFXM7W5Ncn16wmqokgpiKRLuS83KUxyZyv2sUYv";
+const FEE_PAYER: &str = "83astBRguLMdt2h5U1Tpdq5tjFoJ6noeGwaY3mDLVcri";
+const SYSTEM_PROGRAM: &str = "11111111111111111111111111111111";
+const BLOCKHASH: &str = "EkSnNWid2cvwEVnVx9aBqawnmiCNiDgp3gUdkDPTKN1N";
+
+fn transaction_meta() -> Value {
+    json!({
+        "err": null,
+        "status": { "Ok": null },
+        "fee": 5000,
+        "preBalances": [10_000, 1],
+        "postBalances": [5000, 1],
+        "innerInstructions": [],
+        "logMessages": [],
+        "preTokenBalances": [],
+        "postTokenBalances": [],
+        "loadedAddresses": { "writable": [], "readonly": [] },
+        "rewards": [],
+        "computeUnitsConsumed": 150,
+    })
+}
+
+/// Subscribe with `config`, receive one notification with `result`
+async fn transaction_notification(
+    config: TransactionSubscribeConfig,
+    result: Value,
+) -> ClientResult<TransactionNotification> {
+    let server = MockServer::start().await.unwrap();
+    let client = WhirligigClient::connect(&server.endpoint()).await.unwrap();
+
+    let mut stream = client
+        .transaction_subscribe(TransactionSubscribeFilter::default(), config)
+        .await
+        .unwrap();
+    server.notify("transactionSubscribe", result);
+    timeout(TIMEOUT, stream.next()).await.unwrap().unwrap()
+}
+
+fn encoding_config(encoding: UiTransactionEncoding) -> TransactionSubscribeConfig {
+    TransactionSubscribeConfig {
+        encoding: Some(encoding),
+        transaction_details: Some(TransactionDetails::Full),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn transaction_subscribe_base64() {
+    let notification = transaction_notification(
+        encoding_config(UiTransactionEncoding::Base64),
+        json!({
+            "signature": SIGNATURE,
+            "slot": 1,
+            "transaction": {
+                "transaction": ["AQID", "base64"],
+                "meta": transaction_meta(),
+            },
+        }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(notification.signature, SIGNATURE);
+    assert_eq!(notification.slot, 1);
+    let transaction = notification.transaction.unwrap();
+    assert!(
+        matches!(
+            &transaction.transaction,
+            EncodedTransaction::Binary(data, TransactionBinaryEncoding::Base64) if data == "AQID"
+        ),
+        "{transaction:?}"
+    );
+    assert_eq!(transaction.meta.unwrap().fee, 5000);
+}
+
+#[tokio::test]
+async fn transaction_subscribe_json() {
+    let notification = transaction_notification(
+        encoding_config(UiTransactionEncoding::Json),
+        json!({
+            "signature": SIGNATURE,
+            "slot": 2,
+            "transaction": {
+                "transaction": {
+                    "signatures": [SIGNATURE],
+                    "message": {
+                        "header": {
+                            "numRequiredSignatures": 1,
+                            "numReadonlySignedAccounts": 0,
+                            "numReadonlyUnsignedAccounts": 1,
+                        },
+                        "accountKeys": [FEE_PAYER, SYSTEM_PROGRAM],
+                        "recentBlockhash": BLOCKHASH,
+                        "instructions": [{
+                            "programIdIndex": 1,
+                            "accounts": [0],
+                            "data": "3Bxs4h24hBtQy9rw",
+                            "stackHeight": null,
+                        }],
+                    },
+                },
+                "meta": transaction_meta(),
+                "version": "legacy",
+            },
+        }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(notification.slot, 2);
+    let transaction = notification.transaction.unwrap();
+    let EncodedTransaction::Json(ui_transaction) = &transaction.transaction else {
+        panic!("unexpected transaction: {transaction:?}");
+    };
+    assert_eq!(ui_transaction.signatures, [SIGNATURE]);
+    let UiMessage::Raw(message) = &ui_transaction.message else {
+        panic!("unexpected message: {:?}", ui_transaction.message);
+    };
+    assert_eq!(message.account_keys, [FEE_PAYER, SYSTEM_PROGRAM]);
+    assert_eq!(message.instructions[0].program_id_index, 1);
+}
+
+#[tokio::test]
+async fn transaction_subscribe_json_parsed() {
+    let notification = transaction_notification(
+        encoding_config(UiTransactionEncoding::JsonParsed),
+        json!({
+            "signature": SIGNATURE,
+            "slot": 3,
+            "transaction": {
+                "transaction": {
+                    "signatures": [SIGNATURE],
+                    "message": {
+                        "accountKeys": [
+                            {
+                                "pubkey": FEE_PAYER,
+                                "writable": true,
+                                "signer": true,
+                                "source": "transaction",
+                            },
+                            {
+                                "pubkey": SYSTEM_PROGRAM,
+                                "writable": false,
+                                "signer": false,
+                                "source": "transaction",
+                            },
+                        ],
+                        "recentBlockhash": BLOCKHASH,
+                        "instructions": [{
+                            "program": "system",
+                            "programId": SYSTEM_PROGRAM,
+                            "parsed": {
+                                "type": "transfer",
+                                "info": {
+                                    "source": FEE_PAYER,
+                                    "destination": FEE_PAYER,
+                                    "lamports": 1,
+                                },
+                            },
+                            "stackHeight": null,
+                        }],
+                    },
+                },
+                "meta": transaction_meta(),
+                "version": 0,
+            },
+        }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(notification.slot, 3);
+    let transaction = notification.transaction.unwrap();
+    let EncodedTransaction::Json(ui_transaction) = &transaction.transaction else {
+        panic!("unexpected transaction: {transaction:?}");
+    };
+    let UiMessage::Parsed(message) = &ui_transaction.message else {
+        panic!("unexpected message: {:?}", ui_transaction.message);
+    };
+    assert_eq!(message.account_keys[0].pubkey, FEE_PAYER);
+    assert!(message.account_keys[0].signer);
+    assert!(
+        matches!(
+            &message.instructions[0],
+            UiInstruction::Parsed(UiParsedInstruction::Parsed(instruction))
+                if instruction.program == "system"
+        ),
+        "{:?}",
+        message.instructions
+    );
+}
+
+#[tokio::test]
+async fn transaction_subscribe_without_transaction() {
+    // `transaction` is absent with `signatures` and `none` details
+    for transaction_details in [TransactionDetails::Signatures, TransactionDetails::None] {
+        let config = TransactionSubscribeConfig {
+            transaction_details: Some(transaction_details),
+            ..Default::default()
+        };
+        let notification =
+            transaction_notification(config, json!({ "signature": SIGNATURE, "slot": 4 }))
+                .await
+                .unwrap();
+        assert_eq!(
+            notification,
+            TransactionNotification {
+                signature: SIGNATURE.to_owned(),
+                slot: 4,
+                transaction: None,
+            }
+        );
+    }
+}
+
+#[tokio::test]
+async fn transaction_subscribe_invalid() {
+    for result in [
+        json!({ "signature": SIGNATURE }),
+        json!({ "signature": SIGNATURE, "slot": 5, "transaction": "AQID" }),
+    ] {
+        let error = transaction_notification(TransactionSubscribeConfig::default(), result)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(error, ClientError::InvalidNotification { ref method, .. } if method == "transactionNotification"),
+            "{error:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn transaction_subscribe_deprecated() {
+    let server = MockServer::start().await.unwrap();
+    let client = WhirligigClient::connect(&server.endpoint()).await.unwrap();
+
+    let mut stream = client
+        .transaction_subscribe_deprecated(
+            TransactionSubscribeFilterDeprecated::default(),
+            CommitmentConfig::confirmed(),
+        )
+        .await
+        .unwrap();
+    server.notify(
+        "transactionSubscribe",
+        json!({
+            "signature": SIGNATURE,
+            "slot": 6,
+            "transaction": {
+                "transaction": ["AQID", "base64"],
+                "meta": transaction_meta(),
+            },
+        }),
+    );
+    let notification = timeout(TIMEOUT, stream.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(notification.slot, 6);
+    assert!(notification.transaction.is_some());
+}
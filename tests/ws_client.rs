@@ -0,0 +1,97 @@
+use {
+    serde_json::{json, Value},
+    std::{
+        fs,
+        process::{Command, Stdio},
+        time::Duration,
+    },
+    tokio::time::{sleep, timeout},
+    whirligig_client::mock::MockServer,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[tokio::test]
+async fn get_version() {
+    let server = MockServer::start().await.unwrap();
+    let endpoint = server.endpoint();
+
+    let output = tokio::task::spawn_blocking(move || {
+        Command::new(env!("CARGO_BIN_EXE_ws-client"))
+            .args(["--endpoint", &endpoint, "get-version"])
+            .output()
+    })
+    .await
+    .unwrap()
+    .unwrap();
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("solana_core: 1.17.0"));
+}
+
+#[tokio::test]
+async fn subscribe() {
+    let server = MockServer::start().await.unwrap();
+    let child = Command::new(env!("CARGO_BIN_EXE_ws-client"))
+        .args(["--endpoint", &server.endpoint()])
+        .args(["subscribe", "--output", "json", "slot"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let output = tokio::task::spawn_blocking(move || child.wait_with_output());
+
+    timeout(TIMEOUT, async {
+        while server.subscriptions().is_empty() {
+            sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    for slot in 1..=2 {
+        server.notify(
+            "slotSubscribe",
+            json!({ "slot": slot, "parent": slot - 1, "root": 0 }),
+        );
+    }
+    // notifications are sent before close on the same connection
+    server.disconnect();
+
+    let output = timeout(TIMEOUT, output).await.unwrap().unwrap().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("connection closed"));
+    let items = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0]["kind"], "slot");
+    assert_eq!(items[1]["item"]["slot"], 2);
+}
+
+#[test]
+fn subscribe_config_unknown_field() {
+    let path = std::env::temp_dir().join(format!(
+        "whirligig-client-{}-subscriptions.toml",
+        std::process::id()
+    ));
+    for (entry, field) in [
+        ("kind = \"slot\"\ncomitment = \"confirmed\"", "comitment"),
+        (
+            "kind = \"account\"\npubkey = \"11111111111111111111111111111111\"\nencodng = \"base64\"",
+            "encodng",
+        ),
+    ] {
+        fs::write(&path, format!("[[subscriptions]]\nname = \"typo\"\n{entry}\n")).unwrap();
+        // config is loaded before connecting, endpoint is not used
+        let output = Command::new(env!("CARGO_BIN_EXE_ws-client"))
+            .args(["subscribe", "--config", path.to_str().unwrap()])
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(&format!("unknown field `{field}`")), "{stderr}");
+    }
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,241 @@
+use {
+    futures::StreamExt,
+    serde_json::json,
+    solana_rpc_client_api::response::SlotInfo,
+    solana_sdk::pubkey::Pubkey,
+    std::{
+        fs,
+        net::TcpListener,
+        path::{Path, PathBuf},
+        process::{Child, Command, Stdio},
+        time::Duration,
+    },
+    tokio::time::{sleep, timeout, Instant},
+    whirligig_client::{
+        capture::{CaptureFrame, CaptureRecord, CaptureSubscription, CaptureWriter},
+        client::{ClientResult, WhirligigClient},
+        mock::MockServer,
+    },
+};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Replay server process, killed on drop
+struct Replay(Child);
+
+impl Drop for Replay {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+impl Replay {
+    /// Start server on random port and connect to it
+    async fn start(capture: &Path, args: &[&str]) -> (Self, WhirligigClient) {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let replay = Self(
+            Command::new(env!("CARGO_BIN_EXE_ws-replay"))
+                .args(["--capture", capture.to_str().unwrap()])
+                .args(["--listen", &addr.to_string()])
+                .args(args)
+                .stderr(Stdio::null())
+                .spawn()
+                .unwrap(),
+        );
+
+        let endpoint = format!("ws://{addr}/");
+        let client = timeout(TIMEOUT, async {
+            loop {
+                match WhirligigClient::connect(&endpoint).await {
+                    Ok(client) => break client,
+                    Err(_) => sleep(Duration::from_millis(50)).await,
+                }
+            }
+        })
+        .await
+        .unwrap();
+        (replay, client)
+    }
+}
+
+fn capture_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "whirligig-client-{}-{name}.cap",
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+fn slot_notification(subscription: u64, slot: u64) -> CaptureFrame {
+    let message = json!({
+        "jsonrpc": "2.0",
+        "method": "slotNotification",
+        "params": {
+            "result": { "slot": slot, "parent": slot - 1, "root": 0 },
+            "subscription": subscription,
+        }
+    });
+    CaptureFrame::Text(message.to_string())
+}
+
+fn slots(notifications: Vec<ClientResult<SlotInfo>>) -> Vec<u64> {
+    notifications
+        .into_iter()
+        .map(|slot| slot.unwrap().slot)
+        .collect()
+}
+
+#[tokio::test]
+async fn replay_sessions() {
+    let path = capture_path("replay");
+    // second session restarts monotonic time and server side ids
+    for (session_start, session_slots) in
+        [(Duration::from_secs(60), [1, 2]), (Duration::ZERO, [3, 4])]
+    {
+        let mut writer = CaptureWriter::open(&path, false).unwrap();
+        for (index, slot) in session_slots.into_iter().enumerate() {
+            writer
+                .write(&CaptureRecord {
+                    session: 0,
+                    monotonic: session_start + Duration::from_millis(100) * index as u32,
+                    wall_clock: Duration::ZERO,
+                    frame: slot_notification(1, slot),
+                })
+                .unwrap();
+        }
+        writer.flush().unwrap();
+    }
+
+    let (_replay, client) = Replay::start(&path, &[]).await;
+
+    // subscriptions of every session are replayed by subscribe order
+    let ts = Instant::now();
+    let first = client.slot_subscribe().await.unwrap();
+    let second = client.slot_subscribe().await.unwrap();
+    let (first, second) = timeout(
+        TIMEOUT,
+        futures::future::join(
+            first.take(2).collect::<Vec<_>>(),
+            second.take(2).collect::<Vec<_>>(),
+        ),
+    )
+    .await
+    .unwrap();
+    assert_eq!(slots(first), [1, 2]);
+    assert_eq!(slots(second), [3, 4]);
+    // recorded pace within sessions, gap between sessions is skipped
+    let elapsed = ts.elapsed();
+    assert!(elapsed >= Duration::from_millis(200), "{elapsed:?}");
+    assert!(elapsed < Duration::from_secs(2), "{elapsed:?}");
+
+    fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn replay_by_params() {
+    let path = capture_path("params");
+    let pubkeys = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let mut writer = CaptureWriter::open(&path, false).unwrap();
+    let mut write = |monotonic, frame| {
+        writer
+            .write(&CaptureRecord {
+                session: 0,
+                monotonic,
+                wall_clock: Duration::ZERO,
+                frame,
+            })
+            .unwrap();
+    };
+    // recorded in order B, A, only B has notification
+    for (id, pubkey) in pubkeys.iter().rev().enumerate() {
+        write(
+            Duration::ZERO,
+            CaptureFrame::Subscription(CaptureSubscription {
+                id: id as u64 + 1,
+                method: "accountSubscribe".to_owned(),
+                params: json!([pubkey.to_string(), null]),
+                subscription: id as u64 + 1,
+            }),
+        );
+    }
+    let message = json!({
+        "jsonrpc": "2.0",
+        "method": "accountNotification",
+        "params": {
+            "result": {
+                "context": { "slot": 1 },
+                "value": {
+                    "lamports": 7,
+                    "data": ["", "base64"],
+                    "owner": Pubkey::default().to_string(),
+                    "executable": false,
+                    "rentEpoch": 0,
+                    "space": 0,
+                },
+            },
+            "subscription": 1,
+        }
+    });
+    write(Duration::ZERO, CaptureFrame::Text(message.to_string()));
+    writer.flush().unwrap();
+    drop(writer);
+
+    let (_replay, client) = Replay::start(&path, &["--fast"]).await;
+    let mut first = client.account_subscribe(&pubkeys[0], None).await.unwrap();
+    let mut second = client.account_subscribe(&pubkeys[1], None).await.unwrap();
+    let update = timeout(TIMEOUT, second.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(update.value.lamports, 7);
+    // A is recorded without notifications, nothing is replayed
+    assert!(timeout(Duration::from_millis(200), first.next())
+        .await
+        .is_err());
+
+    fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn replay_recorded() {
+    let path = capture_path("recorded");
+    let server = MockServer::start().await.unwrap();
+    let child = Command::new(env!("CARGO_BIN_EXE_ws-client"))
+        .args(["--endpoint", &server.endpoint()])
+        .args(["subscribe", "--record", path.to_str().unwrap(), "slot"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let recorder = tokio::task::spawn_blocking(move || child.wait_with_output());
+    timeout(TIMEOUT, async {
+        while server.subscriptions().is_empty() {
+            sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    for slot in 1..=3 {
+        server.notify(
+            "slotSubscribe",
+            json!({ "slot": slot, "parent": slot - 1, "root": 0 }),
+        );
+    }
+    server.disconnect();
+    timeout(TIMEOUT, recorder).await.unwrap().unwrap().unwrap();
+
+    let (_replay, client) = Replay::start(&path, &["--fast"]).await;
+    let stream = client.slot_subscribe().await.unwrap();
+    let notifications = timeout(TIMEOUT, stream.take(3).collect::<Vec<_>>())
+        .await
+        .unwrap();
+    assert_eq!(slots(notifications), [1, 2, 3]);
+
+    fs::remove_file(path).unwrap();
+}
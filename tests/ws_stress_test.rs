@@ -0,0 +1,58 @@
+use {
+    std::{process::Command, time::Duration},
+    tokio::time::timeout,
+    whirligig_client::mock::MockServer,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+async fn run(endpoint: String, args: &'static [&'static str]) -> std::process::Output {
+    let output = tokio::task::spawn_blocking(move || {
+        Command::new(env!("CARGO_BIN_EXE_ws-stress-test"))
+            .args(["--endpoint", &endpoint, "--duration", "2s"])
+            .args(args)
+            .output()
+    });
+    timeout(TIMEOUT, output).await.unwrap().unwrap().unwrap()
+}
+
+#[tokio::test]
+async fn duration_thresholds() {
+    let server = MockServer::start().await.unwrap();
+
+    let (passed, failed) = tokio::join!(
+        run(
+            server.endpoint(),
+            &["--max-errors", "0", "--max-disconnects", "0"]
+        ),
+        run(server.endpoint(), &["--min-messages-per-sec", "1"]),
+    );
+
+    assert!(passed.status.success(), "{passed:?}");
+    let stdout = String::from_utf8_lossy(&passed.stdout);
+    assert!(stdout.contains("threshold max_errors"), "{stdout}");
+
+    // mock server does not send notifications without the test
+    assert!(!failed.status.success());
+    let stderr = String::from_utf8_lossy(&failed.stderr);
+    assert!(
+        stderr.contains("failed thresholds: min_messages_per_sec"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn connect_rate_validation() {
+    for rate in ["0", "-1", "1e-20", "NaN"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_ws-stress-test"))
+            .arg(format!("--connect-rate={rate}"))
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("at least one connection per day"),
+            "{rate}: {stderr}"
+        );
+    }
+}